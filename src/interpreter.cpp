@@ -0,0 +1,334 @@
+#ifndef INTERPRETER_CPP
+#define INTERPRETER_CPP
+
+#include <bits/stdc++.h>
+
+#include "parser.cpp"
+
+using namespace std;
+
+const string LITERAL_TYPE_NAMES[] = {"Int",  "Float", "Bool",  "String", "Char",
+                                     "Null", "Bytes", "Byte", "Number"};
+
+class EvalError {
+ public:
+  int line;
+  string details;
+  EvalError(int line, string details) {
+    this->line = line;
+    this->details = details;
+  }
+  string to_str() {
+    stringstream s;
+    s << "EvalError {line: " << line << ", error: " << details << "}";
+    return s.str();
+  }
+};
+
+class CompileError {
+ public:
+  int line;
+  string stage;
+  string details;
+  CompileError(int line, string stage, string details) {
+    this->line = line;
+    this->stage = stage;
+    this->details = details;
+  }
+  string to_str() {
+    stringstream s;
+    s << "CompileError {line: " << line << ", stage: " << stage
+      << ", error: " << details << "}";
+    return s.str();
+  }
+};
+
+class Interpreter {
+ public:
+  unordered_map<string, Literal *> env;
+  vector<EvalError> errors;
+  ostream *out = &cout;
+
+  static string withoutSeparators(string value) {
+    value.erase(remove_if(value.begin(), value.end(),
+                          [](char c) {
+                            return !isalnum(c) && c != '.' && c != '+' &&
+                                   c != '-';
+                          }),
+                value.end());
+    return value;
+  }
+
+  static long long intOf(Literal *literal) {
+    string value = withoutSeparators(literal->value);
+    int base = 10;
+    if (value.size() > 2 && value[0] == '0' && isalpha(value[1])) {
+      base = tolower(value[1]) == 'x' ? 16 : tolower(value[1]) == 'o' ? 8 : 2;
+      value.erase(0, 2);
+    }
+    try {
+      return stoll(value, nullptr, base);
+    } catch (out_of_range &) {
+      throw EvalError(literal->line, "Integer literal out of range");
+    }
+  }
+
+  static double floatOf(Literal *literal) {
+    if (literal->type == LiteralType::Int) {
+      return (double)intOf(literal);
+    }
+    return strtod(withoutSeparators(literal->value).c_str(), nullptr);
+  }
+
+  static Literal *makeInt(int line, long long value) {
+    return new Literal(line, LiteralType::Int, to_string(value));
+  }
+
+  static Literal *makeFloat(int line, double value) {
+    char buffer[64];
+    auto end = to_chars(buffer, buffer + sizeof(buffer), value).ptr;
+    return new Literal(line, LiteralType::Float, string(buffer, end));
+  }
+
+  static Literal *makeBool(int line, bool value) {
+    return new Literal(line, LiteralType::Bool, value ? "true" : "false");
+  }
+
+  template <typename T>
+  static Literal *compare(int line, string op, T lhs, T rhs) {
+    if (op == "==") {
+      return makeBool(line, lhs == rhs);
+    } else if (op == "<") {
+      return makeBool(line, lhs < rhs);
+    } else if (op == ">") {
+      return makeBool(line, lhs > rhs);
+    } else if (op == "<=") {
+      return makeBool(line, lhs <= rhs);
+    } else if (op == ">=") {
+      return makeBool(line, lhs >= rhs);
+    }
+    return nullptr;
+  }
+
+  static Literal *evalInt(int line, string op, long long lhs, long long rhs) {
+    long long result;
+    if (auto cmp = compare(line, op, lhs, rhs)) {
+      return cmp;
+    } else if (op == "+" || op == "-" || op == "*") {
+      bool overflow = op == "+"   ? __builtin_add_overflow(lhs, rhs, &result)
+                      : op == "-" ? __builtin_sub_overflow(lhs, rhs, &result)
+                                  : __builtin_mul_overflow(lhs, rhs, &result);
+      if (overflow) {
+        throw EvalError(line, "Integer overflow");
+      }
+      return makeInt(line, result);
+    } else if (op == "/" || op == "%") {
+      if (rhs == 0) {
+        throw EvalError(line, "Division by zero");
+      } else if (lhs == LLONG_MIN && rhs == -1) {
+        throw EvalError(line, "Integer overflow");
+      }
+      return makeInt(line, op == "/" ? lhs / rhs : lhs % rhs);
+    } else if (op == "&") {
+      return makeInt(line, lhs & rhs);
+    } else if (op == "|") {
+      return makeInt(line, lhs | rhs);
+    } else if (op == "^") {
+      return makeInt(line, lhs ^ rhs);
+    } else if (op == "<<" || op == ">>") {
+      if (rhs < 0 || rhs > 63) {
+        throw EvalError(line, "Shift amount out of range");
+      }
+      if (op == ">>") {
+        return makeInt(line, lhs >> rhs);
+      }
+      // shifting a negative value left is undefined, so shift its bits, and
+      // a result that doesn't shift back lost bits like `*` would overflow
+      long long result = (long long)((unsigned long long)lhs << rhs);
+      if ((result >> rhs) != lhs) {
+        throw EvalError(line, "Integer overflow");
+      }
+      return makeInt(line, result);
+    }
+    return nullptr;
+  }
+
+  static Literal *evalFloat(int line, string op, double lhs, double rhs) {
+    if (auto cmp = compare(line, op, lhs, rhs)) {
+      return cmp;
+    } else if (op == "+") {
+      return makeFloat(line, lhs + rhs);
+    } else if (op == "-") {
+      return makeFloat(line, lhs - rhs);
+    } else if (op == "*") {
+      return makeFloat(line, lhs * rhs);
+    } else if (op == "/") {
+      return makeFloat(line, lhs / rhs);
+    } else if (op == "%") {
+      return makeFloat(line, fmod(lhs, rhs));
+    }
+    return nullptr;
+  }
+
+  static Literal *evalBinary(string op, Literal *lhs, Literal *rhs) {
+    int line = lhs->line;
+    auto numeric = [](Literal *literal) {
+      return literal->type == LiteralType::Int ||
+             literal->type == LiteralType::Float;
+    };
+    Literal *result = nullptr;
+    if (lhs->type == LiteralType::Int && rhs->type == LiteralType::Int) {
+      result = evalInt(line, op, intOf(lhs), intOf(rhs));
+    } else if (numeric(lhs) && numeric(rhs)) {
+      result = evalFloat(line, op, floatOf(lhs), floatOf(rhs));
+    } else if (lhs->type == LiteralType::String &&
+               rhs->type == LiteralType::String) {
+      result = op == "+" ? new Literal(line, LiteralType::String,
+                                       lhs->value + rhs->value)
+                         : compare(line, op, lhs->value, rhs->value);
+    } else if (lhs->type == LiteralType::Bool &&
+               rhs->type == LiteralType::Bool) {
+      bool l = lhs->value == "true", r = rhs->value == "true";
+      if (op == "&&" || op == "&") {
+        result = makeBool(line, l && r);
+      } else if (op == "||" || op == "|") {
+        result = makeBool(line, l || r);
+      } else if (op == "^") {
+        result = makeBool(line, l != r);
+      } else if (op == "==") {
+        result = makeBool(line, l == r);
+      }
+    } else if (lhs->type == LiteralType::Char &&
+               rhs->type == LiteralType::Char && op == "==") {
+      result = makeBool(line, lhs->value == rhs->value);
+    } else if (lhs->type == LiteralType::Null &&
+               rhs->type == LiteralType::Null && op == "==") {
+      result = makeBool(line, true);
+    }
+    if (!result) {
+      throw EvalError(line, "Cannot apply `" + op + "` to " +
+                                LITERAL_TYPE_NAMES[(int)lhs->type] + " and " +
+                                LITERAL_TYPE_NAMES[(int)rhs->type]);
+    }
+    return result;
+  }
+
+  static Literal *evalUnary(int line, string op, Literal *operand) {
+    if (op == "-" && operand->type == LiteralType::Int) {
+      long long value = intOf(operand);
+      if (value == LLONG_MIN) {
+        throw EvalError(line, "Integer overflow");
+      }
+      return makeInt(line, -value);
+    } else if (op == "-" && operand->type == LiteralType::Float) {
+      return makeFloat(line, -floatOf(operand));
+    } else if (op == "~" && operand->type == LiteralType::Int) {
+      return makeInt(line, ~intOf(operand));
+    } else if (op == "!" && operand->type == LiteralType::Bool) {
+      return makeBool(line, operand->value != "true");
+    }
+    throw EvalError(line, "Cannot apply `" + op + "` to " +
+                              LITERAL_TYPE_NAMES[(int)operand->type]);
+  }
+
+  Literal *eval(Expr *expr) {
+    if (auto literal = dynamic_cast<LiteralExpr *>(expr)) {
+      return literal->value;
+    } else if (auto variable = dynamic_cast<VariableExpr *>(expr)) {
+      if (auto it = env.find(variable->name); it != env.end()) {
+        return it->second;
+      }
+      throw EvalError(expr->line,
+                      "Undefined variable `" + variable->name + "`");
+    } else if (auto binary = dynamic_cast<BinaryExpr *>(expr)) {
+      Literal *lhs = eval(binary->lhs);
+      if ((binary->op == "&&" || binary->op == "||") &&
+          lhs->type == LiteralType::Bool &&
+          (lhs->value == "true") == (binary->op == "||")) {
+        return lhs;
+      }
+      return evalBinary(binary->op, lhs, eval(binary->rhs));
+    } else if (auto unary = dynamic_cast<UnaryExpr *>(expr)) {
+      return evalUnary(expr->line, unary->op, eval(unary->operand));
+    } else if (auto ternary = dynamic_cast<TernaryExpr *>(expr)) {
+      return eval(condition(ternary->cond) ? ternary->then
+                                           : ternary->otherwise);
+    } else if (auto assign = dynamic_cast<AssignExpr *>(expr)) {
+      auto variable = dynamic_cast<VariableExpr *>(assign->target);
+      if (!variable) {
+        throw EvalError(expr->line, "Unsupported assignment target");
+      }
+      if (!env.count(variable->name)) {
+        throw EvalError(expr->line,
+                        "Undefined variable `" + variable->name + "`");
+      }
+      return env[variable->name] = eval(assign->value);
+    }
+    throw EvalError(expr->line, "Unsupported expression");
+  }
+
+  static Literal *evaluate(string input) {
+    Lexer lexer(input);
+    lexer.lex();
+    for (auto token : lexer.tokens) {
+      if (auto invalid = dynamic_cast<Invalid *>(token)) {
+        throw CompileError(invalid->line, "lex", invalid->details);
+      }
+    }
+    Parser parser(lexer.tokens);
+    Expr *expr = parser.parse();
+    if (!expr) {
+      auto &error = parser.errors.front();
+      throw CompileError(error.line, "parse", error.details);
+    }
+    try {
+      return Interpreter().eval(expr);
+    } catch (EvalError &error) {
+      throw CompileError(error.line, "eval", error.details);
+    }
+  }
+
+  bool condition(Expr *expr) {
+    Literal *cond = eval(expr);
+    if (cond->type != LiteralType::Bool) {
+      throw EvalError(expr->line, "Condition must be Bool, not " +
+                                      LITERAL_TYPE_NAMES[(int)cond->type]);
+    }
+    return cond->value == "true";
+  }
+
+  void exec(Stmt *stmt) {
+    if (auto branch = dynamic_cast<IfStmt *>(stmt)) {
+      for (auto inner : condition(branch->cond) ? branch->then
+                                                : branch->otherwise) {
+        exec(inner);
+      }
+    } else if (auto loop = dynamic_cast<WhileStmt *>(stmt)) {
+      while (condition(loop->cond)) {
+        for (auto inner : loop->body) {
+          exec(inner);
+        }
+      }
+    } else if (auto let = dynamic_cast<LetStmt *>(stmt)) {
+      env[let->name] = eval(let->value);
+    } else if (auto print = dynamic_cast<PrintStmt *>(stmt)) {
+      *out << eval(print->value)->value << endl;
+    } else if (auto expr = dynamic_cast<ExprStmt *>(stmt)) {
+      eval(expr->expr);
+    }
+  }
+
+  bool run(vector<Stmt *> program) {
+    try {
+      for (auto stmt : program) {
+        exec(stmt);
+      }
+      return true;
+    } catch (EvalError &error) {
+      errors.push_back(error);
+      return false;
+    }
+  }
+};
+
+#endif
@@ -1,3 +1,6 @@
+#ifndef LEXER_CPP
+#define LEXER_CPP
+
 #include <bits/stdc++.h>
 using namespace std;
 
@@ -5,11 +8,13 @@ const unordered_map<string, int> KEYWORDS{
     {"if", 0},     {"else", 1},   {"import", 2},    {"fn", 3},
     {"struct", 4}, {"let", 5},    {"mod", 6},       {"const", 7},
     {"enum", 8},   {"return", 9}, {"while", 10},    {"for", 11},
-    {"in", 12},    {"break", 13}, {"continue", 14}, {"print", 15}};
+    {"in", 12},    {"break", 13}, {"continue", 14}, {"print", 15},
+    {"extern", 16}, {"as", 17}};
 
 const string REVERSE_KEYWORD[] = {
     "if",   "else",   "import", "fn",  "struct", "let",   "mod",      "const",
-    "enum", "return", "while",  "for", "in",     "break", "continue", "print"};
+    "enum", "return", "while",  "for", "in",     "break", "continue", "print",
+    "extern", "as"};
 
 const unordered_map<string, int> SYMBOLS{
     {"+", 0},  {"-", 1},   {"*", 2},   {"/", 3},   {":", 4},   {".", 5},
@@ -18,21 +23,69 @@ const unordered_map<string, int> SYMBOLS{
     {"!", 18}, {"~", 19},  {">", 20},  {"<", 21},  {">=", 22}, {"<=", 23},
     {"[", 24}, {"]", 25},  {"{", 26},  {"}", 27},  {"(", 28},  {")", 29},
     {"^", 30}, {"%", 31},  {"|=", 32}, {"&=", 33}, {"^=", 34}, {"%", 35},
-    {"%=", 36}};
+    {"%=", 36}, {"=>", 37}, {"++", 38}, {"--", 39}, {"->", 40},
+    {"<<", 41}, {">>", 42}, {",", 43}, {".*", 44}, {"./", 45}, {".^", 46},
+    {"..", 47}, {"..=", 48}, {"\\", 49}, {"?", 50}};
+
+const string REVERSE_SYMBOL[] = {
+    "+",  "-",  "*",  "/",  ":",  ".",  ";",  "::", "==", "+=", "-=",
+    "*=", "=",  "/=", "|",  "||", "&",  "&&", "!",  "~",  ">",  "<",
+    ">=", "<=", "[",  "]",  "{",  "}",  "(",  ")",  "^",  "%",  "|=",
+    "&=", "^=", "%",  "%=", "=>", "++", "--", "->", "<<", ">>", ",",
+    ".*", "./", ".^", "..", "..=", "\\", "?"};
+
+const size_t MAX_SYMBOL_LENGTH = 3;
+
+const unordered_set<string> DIRECTIVES{"define", "undef",  "include",
+                                       "ifdef",  "ifndef", "endif"};
+
+const unordered_set<string> ELEMENTWISE_OPERATORS{".*", "./", ".^"};
+
+const unordered_set<string> INT_SUFFIXES{"i8",  "i16", "i32", "i64",   "u8",
+                                         "u16", "u32", "u64", "usize", "isize"};
+
+const unordered_map<string, string> TEXTUAL_OPERATORS{
+    {"and", "&&"}, {"or", "||"}, {"not", "!"}};
+
+enum class TokenKind {
+  Keyword,
+  Identifier,
+  Literal,
+  Symbol,
+  Invalid,
+  Directive,
+  Attribute,
+  Template,
+  DocComment,
+  Eof
+};
+
+struct Position {
+  int line;
+  int col;
+};
 
 class Token {
  public:
   int line;
+  size_t offset = 0;
+  int col = 0;
+  size_t length = 0;
+  vector<string> leading;
+  vector<string> trailing;
   Token() = delete;
   Token(int line) { this->line = line; }
+  virtual ~Token() = default;
+  virtual TokenKind kind() = 0;
   virtual string to_str() { return "Token {line: something}"; }
 };
 
 class Keyword : public Token {
+ public:
   int id;
 
- public:
   Keyword(int line, int id) : Token(line) { this->id = id; }
+  TokenKind kind() { return TokenKind::Keyword; }
   string to_str() {
     string v = REVERSE_KEYWORD[id];
     stringstream s;
@@ -42,10 +95,11 @@ class Keyword : public Token {
 };
 
 class Identifier : public Token {
+ public:
   string value;
 
- public:
   Identifier(int line, string val) : Token(line) { this->value = val; }
+  TokenKind kind() { return TokenKind::Identifier; }
   string to_str() {
     stringstream s;
     s << "Identifier {line: " << line << ", value: \"" << value << "\"}";
@@ -53,62 +107,409 @@ class Identifier : public Token {
   }
 };
 
+enum class LiteralType {
+  Int,
+  Float,
+  Bool,
+  String,
+  Char,
+  Null,
+  Bytes,
+  Byte,
+  Number
+};
+
 class Literal : public Token {
+ public:
+  LiteralType type;
   string value;
+  string suffix;
 
- public:
-  Literal(int line, string val) : Token(line) { this->value = val; }
+  Literal(int line, LiteralType type, string val) : Token(line) {
+    this->type = type;
+    this->value = val;
+  }
+  TokenKind kind() { return TokenKind::Literal; }
   string to_str() {
     stringstream s;
-    s << "Literal {line: " << line << ", value: " << value << "}";
+    s << "Literal {line: " << line << ", value: " << value;
+    if (!suffix.empty()) {
+      s << ", suffix: " << suffix;
+    }
+    s << "}";
     return s.str();
   }
 };
 
 class Symbol : public Token {
+ public:
   int id;
+  string custom;
 
- public:
   Symbol(int line, int id) : Token(line) { this->id = id; }
+  Symbol(int line, string custom) : Token(line) {
+    this->id = -1;
+    this->custom = custom;
+  }
+  TokenKind kind() { return TokenKind::Symbol; }
+  const string &value() { return id < 0 ? custom : REVERSE_SYMBOL[id]; }
   string to_str() {
-    string v;
-    for (auto &[s, i] : SYMBOLS) {
-      if (id == i) {
-        v = s;
-        break;
-      }
-    }
     stringstream s;
-    s << "Symbol {line: " << line << ", value: \"" << v << "\"}";
+    s << "Symbol {line: " << line << ", value: \"" << value() << "\"}";
     return s.str();
   };
 };
 
+enum class LexErrorKind {
+  Other,
+  Incomplete,
+  UnterminatedString,
+  UnterminatedChar,
+  UnterminatedComment,
+  UnterminatedAttribute,
+  UnterminatedTemplate,
+  UnterminatedHole,
+  InvalidEscape,
+  InvalidNumber,
+  InvalidChar,
+  UnexpectedChar,
+  InvalidUtf8
+};
+
+const string LEX_ERROR_KIND_NAMES[] = {"Other",
+                                       "Incomplete",
+                                       "UnterminatedString",
+                                       "UnterminatedChar",
+                                       "UnterminatedComment",
+                                       "UnterminatedAttribute",
+                                       "UnterminatedTemplate",
+                                       "UnterminatedHole",
+                                       "InvalidEscape",
+                                       "InvalidNumber",
+                                       "InvalidChar",
+                                       "UnexpectedChar",
+                                       "InvalidUtf8"};
+
 class Invalid : public Token {
+ public:
   string details;
+  LexErrorKind error_kind = LexErrorKind::Other;
+  bool incomplete;
+  string text;
+  optional<Position> opener;
 
+  Invalid(int line, string details, bool incomplete = false) : Token(line) {
+    this->details = details;
+    this->incomplete = incomplete;
+  }
+  Invalid(int line, LexErrorKind error_kind, string details,
+          bool incomplete = false)
+      : Invalid(line, details, incomplete) {
+    this->error_kind = error_kind;
+  }
+  TokenKind kind() { return TokenKind::Invalid; }
+  string to_str() {
+    stringstream s;
+    s << "Invalid {line: " << line << ", error: " << details;
+    if (error_kind != LexErrorKind::Other) {
+      s << ", kind: " << LEX_ERROR_KIND_NAMES[(int)error_kind];
+    }
+    if (length) {
+      s << ", at: " << line << ":" << col << ", offset: " << offset
+        << ", length: " << length;
+    }
+    if (!text.empty()) {
+      s << ", text: \"" << text << "\"";
+    }
+    if (opener) {
+      s << ", started: " << opener->line << ":" << opener->col;
+    }
+    s << "}";
+    return s.str();
+  }
+};
+
+class Directive : public Token {
  public:
-  Invalid(int line, string details) : Token(line) { this->details = details; }
+  string name;
+  string args;
+
+  Directive(int line, string name, string args) : Token(line) {
+    this->name = name;
+    this->args = args;
+  }
+  TokenKind kind() { return TokenKind::Directive; }
+  string to_str() {
+    stringstream s;
+    s << "Directive {line: " << line << ", name: \"" << name
+      << "\", args: \"" << args << "\"}";
+    return s.str();
+  }
+};
+
+class Attribute : public Token {
+ public:
+  vector<Token *> tokens;
+
+  Attribute(int line, vector<Token *> tokens) : Token(line) {
+    this->tokens = tokens;
+  }
+  TokenKind kind() { return TokenKind::Attribute; }
   string to_str() {
     stringstream s;
-    s << "Invalid {line: " << line << ", error: " << details << "}";
+    s << "Attribute {line: " << line << ", tokens: [";
+    for (size_t i = 0; i < tokens.size(); i++) {
+      s << (i ? ", " : "") << tokens[i]->to_str();
+    }
+    s << "]}";
     return s.str();
   }
 };
 
+class TemplateString : public Token {
+ public:
+  vector<string> texts;
+  vector<vector<Token *>> holes;
+
+  TemplateString(int line) : Token(line) {}
+  TokenKind kind() { return TokenKind::Template; }
+  string to_str() {
+    stringstream s;
+    s << "TemplateString {line: " << line << ", parts: [\"" << texts[0]
+      << "\"";
+    for (size_t i = 0; i < holes.size(); i++) {
+      s << ", [";
+      for (size_t j = 0; j < holes[i].size(); j++) {
+        s << (j ? ", " : "") << holes[i][j]->to_str();
+      }
+      s << "], \"" << texts[i + 1] << "\"";
+    }
+    s << "]}";
+    return s.str();
+  }
+};
+
+class Eof : public Token {
+ public:
+  Eof(int line) : Token(line) {}
+  TokenKind kind() { return TokenKind::Eof; }
+  string to_str() {
+    stringstream s;
+    s << "Eof {line: " << line << "}";
+    return s.str();
+  }
+};
+
+class DocComment : public Token {
+ public:
+  string text;
+
+  DocComment(int line, string text) : Token(line) { this->text = text; }
+  TokenKind kind() { return TokenKind::DocComment; }
+  string to_str() {
+    stringstream s;
+    s << "DocComment {line: " << line << ", text: \"" << text << "\"}";
+    return s.str();
+  }
+};
+
+class Warning {
+ public:
+  int line;
+  string details;
+  Warning(int line, string details) {
+    this->line = line;
+    this->details = details;
+  }
+  string to_str() {
+    stringstream s;
+    s << "Warning {line: " << line << ", warning: " << details << "}";
+    return s.str();
+  }
+};
+
+enum class IndentKind { None, Tabs, Spaces, Mixed };
+
+enum class InvisiblePolicy { Reject, Warn, Allow };
+
+struct IndentStyle {
+  IndentKind kind;
+  size_t width;
+};
+
+struct TokenStats {
+  size_t total = 0;
+  size_t errors = 0;
+  size_t bytes = 0;
+  map<TokenKind, size_t> kinds;
+};
+
+enum class ByteClass {
+  IdentStart,
+  Digit,
+  StringQuote,
+  CharQuote,
+  Newline,
+  Whitespace,
+  Hash,
+  SymbolStart,
+  Other
+};
+
+struct LexerOptions {
+  bool textual_operators = false;
+  bool directives = false;
+  bool attributes = false;
+  bool template_strings = false;
+  bool doc_comments = false;
+  bool trivia = false;
+  vector<string> line_comment_prefixes = {"#", "//"};
+  pair<string, string> block_comment_delims = {"/*", "*/"};
+  bool numbers_as_raw = false;
+  char digit_separator = '_';
+  bool allow_leading_zeros = true;
+  bool float_words = false;
+  string null_word = "null";
+  unordered_map<string, string> keyword_aliases;
+  bool keyword_case_warnings = false;
+  bool stop_on_error = false;
+  bool recover_to_delimiter = false;
+  bool eof_token = false;
+  InvisiblePolicy invisible_chars = InvisiblePolicy::Reject;
+  bool group_unknown = false;
+  bool elementwise_operators = false;
+  bool backslash_symbol = false;
+  size_t max_operator_length = 3;
+  bool legacy_escapes = false;
+  size_t max_string_length = 0;
+  size_t token_capacity = 0;
+  size_t progress_interval = 0;
+  function<void(size_t, size_t)> progress;
+};
+
+struct GrammarTables {
+  vector<string> keywords;
+  vector<string> symbols;
+  vector<pair<string, string>> literal_patterns;
+};
+
+// the compact format is only built with -DLEXER_COMPACT_TOKENS
+#ifdef LEXER_COMPACT_TOKENS
+struct CompactTokens {
+  vector<uint8_t> kinds;
+  vector<uint32_t> offsets;
+
+  string toBytes() {
+    string bytes;
+    auto put = [&bytes](uint32_t value) {
+      for (int shift = 0; shift < 32; shift += 8) {
+        bytes.push_back((char)((value >> shift) & 0xff));
+      }
+    };
+    put(kinds.size());
+    bytes.append(kinds.begin(), kinds.end());
+    for (auto offset : offsets) {
+      put(offset);
+    }
+    return bytes;
+  }
+
+  static bool fromBytes(const string &bytes, CompactTokens &out) {
+    size_t pos = 0;
+    auto get = [&bytes, &pos]() {
+      uint32_t value = 0;
+      for (int shift = 0; shift < 32; shift += 8) {
+        value |= (uint32_t)(uint8_t)bytes[pos++] << shift;
+      }
+      return value;
+    };
+    if (bytes.size() < 4) {
+      return false;
+    }
+    size_t count = get();
+    if (bytes.size() != 4 + count * 5) {
+      return false;
+    }
+    out.kinds.assign(bytes.begin() + 4, bytes.begin() + 4 + count);
+    pos += count;
+    out.offsets.clear();
+    for (size_t i = 0; i < count; i++) {
+      out.offsets.push_back(get());
+    }
+    return true;
+  }
+};
+#endif
+
+class Cursor {
+ public:
+  const string *source;
+  size_t offset = 0;
+  int line = 1;
+  size_t line_start = 0;
+
+  Cursor(const string *source) { this->source = source; }
+
+  bool done() { return offset >= source->size(); }
+
+  char peek(size_t ahead = 0) {
+    return offset + ahead < source->size() ? (*source)[offset + ahead] : '\0';
+  }
+
+  void advance(size_t count = 1) {
+    size_t end = min(offset + count, source->size());
+    for (; offset < end; offset++) {
+      if ((*source)[offset] == '\n') {
+        line++;
+        line_start = offset + 1;
+      }
+    }
+  }
+
+  void advanceTo(size_t end) {
+    if (end > offset) {
+      advance(end - offset);
+    }
+  }
+
+  void seek(size_t to) {
+    to = min(to, source->size());
+    if (to < offset) {
+      line -= std::count(source->begin() + to, source->begin() + offset, '\n');
+      offset = to;
+      size_t newline = to == 0 ? string::npos : source->rfind('\n', to - 1);
+      line_start = newline == string::npos ? 0 : newline + 1;
+    }
+    advanceTo(to);
+  }
+
+  int col() { return offset - line_start + 1; }
+
+  Position position() { return {line, col()}; }
+};
+
 class Lexer {
  public:
   string file_contents;
+  LexerOptions options;
   vector<Token *> tokens;
-  size_t idx = 0;
-  int line;
+  vector<Warning> warnings;
+  vector<pair<size_t, size_t>> comments;
+  unordered_set<string> custom_operators;
+  vector<function<Token *(const string &, size_t, size_t &)>> literal_lexers;
+  Cursor cursor{&file_contents};
+  TokenStats stats;
+  // nextKind clears this so string literals skip building their value
+  bool payloads = true;
+  // set once by the constructor; pure ASCII skips the UTF-8 checks in step
+  bool ascii = false;
 
-  char peek() { return file_contents[idx]; }
+  char peek() { return cursor.peek(); }
 
-  char peek_next() { return file_contents[idx + 1]; }
+  char peek_next() { return cursor.peek(1); }
 
   void lexWord() {
-    size_t end = idx;
+    size_t end = cursor.offset;
     while (end < file_contents.size()) {
       char ch = file_contents[end];
       if ((ch >= 'a' && ch <= 'z') || (ch >= 'A' && ch <= 'Z') || ch == '_' ||
@@ -118,153 +519,1505 @@ class Lexer {
         break;
       }
     }
-    string word(file_contents.begin() + idx, file_contents.begin() + end);
-    if (auto id = KEYWORDS.find(word); id != KEYWORDS.end()) {
-      tokens.push_back(new Keyword(line, id->second));
+    string word(file_contents.begin() + cursor.offset,
+                file_contents.begin() + end);
+    if (auto alias = options.keyword_aliases.find(word);
+        alias != options.keyword_aliases.end() &&
+        KEYWORDS.count(alias->second)) {
+      tokens.push_back(new Keyword(cursor.line, KEYWORDS.at(alias->second)));
+    } else if (auto id = KEYWORDS.find(word); id != KEYWORDS.end()) {
+      tokens.push_back(new Keyword(cursor.line, id->second));
+    } else if (word == "true" || word == "false") {
+      tokens.push_back(new Literal(cursor.line, LiteralType::Bool, word));
+    } else if (word == options.null_word) {
+      tokens.push_back(new Literal(cursor.line, LiteralType::Null, word));
+    } else if (options.float_words && (word == "inf" || word == "nan")) {
+      tokens.push_back(new Literal(cursor.line, LiteralType::Float, word));
+    } else if (auto op = TEXTUAL_OPERATORS.find(word);
+               options.textual_operators && op != TEXTUAL_OPERATORS.end()) {
+      tokens.push_back(new Symbol(cursor.line, SYMBOLS.at(op->second)));
+    } else {
+      string folded = word;
+      transform(folded.begin(), folded.end(), folded.begin(), ::tolower);
+      if (options.keyword_case_warnings && KEYWORDS.count(folded)) {
+        warnings.push_back(Warning(cursor.line, "Identifier `" + word +
+                                                    "` differs from keyword `" +
+                                                    folded + "` only by case"));
+      }
+      tokens.push_back(new Identifier(cursor.line, word));
+    }
+    cursor.advanceTo(end);
+  }
+
+  size_t scanNumber() {
+    enum class State { Integer, Fraction, Exponent };
+    State state = State::Integer;
+    size_t end = cursor.offset;
+    auto digit = [this](size_t i) {
+      return i < file_contents.size() && file_contents[i] >= '0' &&
+             file_contents[i] <= '9';
+    };
+    while (true) {
+      char c = end < file_contents.size() ? file_contents[end] : '\0';
+      if (digit(end) || (end > cursor.offset && separator(end) &&
+                         (digit(end - 1) || separator(end - 1)))) {
+        end++;
+      } else if (state == State::Integer && c == '.' && digit(end + 1)) {
+        state = State::Fraction;
+        end++;
+      } else if (state == State::Integer && c == '.' && end > cursor.offset) {
+        char after = end + 1 < file_contents.size() ? file_contents[end + 1]
+                                                    : '\0';
+        if (after == '.' || classifyByte(after) == ByteClass::IdentStart ||
+            (options.elementwise_operators &&
+             ELEMENTWISE_OPERATORS.count(string(".") + after))) {
+          break;
+        }
+        state = State::Fraction;
+        end++;
+      } else if (state != State::Exponent && (c == 'e' || c == 'E')) {
+        size_t exponent = end + 1;
+        if (exponent < file_contents.size() &&
+            (file_contents[exponent] == '+' ||
+             file_contents[exponent] == '-')) {
+          exponent++;
+        }
+        if (!digit(exponent)) {
+          break;
+        }
+        state = State::Exponent;
+        end = exponent;
+      } else {
+        break;
+      }
+    }
+    return end - cursor.offset;
+  }
+
+  void pushNumber(Literal *literal) {
+    size_t end = cursor.offset;
+    while (end < file_contents.size() &&
+           (isalnum(file_contents[end]) || file_contents[end] == '_')) {
+      end++;
+    }
+    string suffix = file_contents.substr(cursor.offset, end - cursor.offset);
+    if (suffix.empty()) {
+      tokens.push_back(literal);
+    } else if (suffix == "f32" || suffix == "f64") {
+      literal->type = LiteralType::Float;
+      literal->suffix = suffix;
+      tokens.push_back(literal);
+    } else if (literal->type == LiteralType::Int &&
+               INT_SUFFIXES.count(suffix)) {
+      literal->suffix = suffix;
+      tokens.push_back(literal);
     } else {
-      tokens.push_back(new Identifier(line, word));
+      tokens.push_back(new Invalid(cursor.line, LexErrorKind::InvalidNumber,
+                                   "Unknown number suffix `" + suffix + "`"));
+    }
+    // numbers_as_raw leaves the value to a bignum library downstream
+    if (options.numbers_as_raw && tokens.back() == literal) {
+      literal->type = LiteralType::Number;
     }
-    idx = end;
+    cursor.advanceTo(end);
+  }
+
+  bool separator(size_t i) {
+    return options.digit_separator && i < file_contents.size() &&
+           file_contents[i] == options.digit_separator;
+  }
+
+  string withoutSeparators(string number) {
+    if (options.digit_separator) {
+      number.erase(remove(number.begin(), number.end(),
+                          options.digit_separator),
+                   number.end());
+    }
+    return number;
+  }
+
+  void lexRadix(int base, const string &digits, string name) {
+    size_t end = cursor.offset + 2;
+    while (end < file_contents.size() &&
+           (digits.find(file_contents[end]) != string::npos ||
+            separator(end))) {
+      end++;
+    }
+    string number = file_contents.substr(cursor.offset, end - cursor.offset);
+    string value = withoutSeparators(number.substr(2));
+    if (end < file_contents.size() && isdigit(file_contents[end])) {
+      while (end < file_contents.size() && isalnum(file_contents[end])) {
+        end++;
+      }
+      tokens.push_back(new Invalid(cursor.line, LexErrorKind::InvalidNumber,
+                                   "Invalid digit in " + name));
+    } else if (value.empty()) {
+      tokens.push_back(new Invalid(cursor.line, LexErrorKind::InvalidNumber,
+                                   "Empty " + name));
+    } else {
+      try {
+        if (!options.numbers_as_raw) {
+          stoll(value, nullptr, base);
+        }
+      } catch (out_of_range &) {
+        tokens.push_back(new Invalid(cursor.line, LexErrorKind::InvalidNumber,
+                                     "Integer literal out of range"));
+        cursor.advanceTo(end);
+        return;
+      }
+      cursor.advanceTo(end);
+      pushNumber(new Literal(cursor.line, LiteralType::Int, number));
+      return;
+    }
+    cursor.advanceTo(end);
   }
 
   void lexNumber() {
-    size_t end;
-    stod(file_contents.substr(idx), &end);
-    tokens.push_back(new Literal(line, file_contents.substr(idx, end)));
-    idx += end;
+    if (peek() == '0' && (peek_next() == 'x' || peek_next() == 'X')) {
+      lexRadix(16, "0123456789abcdefABCDEF", "hex literal");
+      return;
+    } else if (peek() == '0' && (peek_next() == 'b' || peek_next() == 'B')) {
+      lexRadix(2, "01", "binary literal");
+      return;
+    } else if (peek() == '0' && (peek_next() == 'o' || peek_next() == 'O')) {
+      lexRadix(8, "01234567", "octal literal");
+      return;
+    }
+    size_t end = scanNumber();
+    string number = file_contents.substr(cursor.offset, end);
+    size_t next = cursor.offset + end;
+    char c = next < file_contents.size() ? file_contents[next] : '\0';
+    bool exponent = number.find_first_of("eE") != string::npos;
+    if ((c == '.' && next + 1 < file_contents.size() &&
+         isdigit(file_contents[next + 1]) &&
+         (exponent || number.find('.') != string::npos)) ||
+        ((c == 'e' || c == 'E') && exponent)) {
+      Position at = positionOf(next);
+      size_t stop = next + 1;
+      while (stop < file_contents.size() &&
+             (isalnum(file_contents[stop]) || file_contents[stop] == '.')) {
+        stop++;
+      }
+      tokens.push_back(new Invalid(
+          cursor.line, LexErrorKind::InvalidNumber,
+          string("Second `") + c + "` in number literal at " +
+              to_string(at.line) + ":" + to_string(at.col)));
+      cursor.advanceTo(stop);
+      return;
+    }
+    if (c == 'e' || c == 'E') {
+      // scanNumber takes every well formed exponent, so this is 1e, 1e+ or
+      // 1e-x; keep the sign in the bad literal rather than leaving a minus
+      char sign = next + 1 < file_contents.size() ? file_contents[next + 1]
+                                                  : '\0';
+      tokens.push_back(new Invalid(cursor.line, LexErrorKind::InvalidNumber,
+                                   "Missing exponent digits"));
+      cursor.advance(end + (sign == '+' || sign == '-' ? 2 : 1));
+      return;
+    }
+    string value = withoutSeparators(number);
+    auto type = value.find_first_not_of("0123456789") == string::npos
+                    ? LiteralType::Int
+                    : LiteralType::Float;
+    if (!options.allow_leading_zeros && type == LiteralType::Int &&
+        value.size() > 1 && value[0] == '0') {
+      tokens.push_back(new Invalid(cursor.line, LexErrorKind::InvalidNumber,
+                                   "Leading zero in decimal integer"));
+      cursor.advance(end);
+      return;
+    }
+    if (!options.numbers_as_raw) {
+      bool in_range = true;
+      if (type == LiteralType::Int) {
+        try {
+          stoll(value);
+        } catch (out_of_range &) {
+          in_range = false;
+        }
+      } else {
+        // stod throws on underflow too, strtod rounds it to a subnormal or 0
+        in_range = !isinf(strtod(value.c_str(), nullptr));
+      }
+      if (!in_range) {
+        tokens.push_back(new Invalid(cursor.line, LexErrorKind::InvalidNumber,
+                                     type == LiteralType::Int
+                                         ? "Integer literal out of range"
+                                         : "Float literal out of range"));
+        cursor.advance(end);
+        return;
+      }
+    }
+    cursor.advance(end);
+    pushNumber(new Literal(cursor.line, type, number));
   }
 
-  void lexString() {
+  Position positionOf(size_t offset) {
+    Cursor at(&file_contents);
+    at.seek(offset);
+    return at.position();
+  }
+
+  Invalid *unclosed(LexErrorKind error_kind, string details, size_t opener) {
+    Position at = positionOf(opener);
+    auto invalid = new Invalid(at.line, error_kind, details, true);
+    invalid->offset = opener;
+    invalid->col = at.col;
+    invalid->opener = at;
+    return invalid;
+  }
+
+  void lexString(bool bytes = false) {
     string s;
-    auto l = line;
-    size_t i;
-    for (i = idx + 1; i < file_contents.size() - 1; i++) {
-      char c = file_contents[i];
-      if (c == '"') {
+    auto l = cursor.line;
+    size_t start = cursor.offset;
+    int bad_escape = 0, non_ascii = 0, bad_utf8 = 0;
+    cursor.advance(bytes ? 2 : 1);
+    while (!cursor.done() && cursor.peek() != '"') {
+      char c = cursor.peek();
+      cursor.advance();
+      if (c != '\\') {
+        if (bytes && (unsigned char)c > 0x7F && !non_ascii) {
+          non_ascii = cursor.line;
+        } else if (!bytes && (unsigned char)c > 0x7F) {
+          // take a whole sequence from its lead byte, so a continuation
+          // byte only gets here when nothing leads it
+          size_t length = utf8Length(cursor.offset - 1);
+          if (length > 1) {
+            if (payloads) {
+              s += file_contents.substr(cursor.offset - 1, length);
+            }
+            cursor.advance(length - 1);
+            continue;
+          } else if (!bad_utf8) {
+            bad_utf8 = cursor.line;
+          }
+        }
+        if (payloads) {
+          s.push_back(c);
+        }
+        continue;
+      } else if (cursor.done()) {
         break;
-      } else if (c == '\n') {
-        line += 1;
+      }
+      c = cursor.peek();
+      if (c == '\\' || c == '"') {
+        s.push_back(c);
+      } else if (c == 'n') {
         s.push_back('\n');
-      } else if (c == '\\') {
-        c = file_contents[++i];
-        if (c == 'n') {
-          s.push_back('\n');
-        } else if (c == 't') {
-          s.push_back('\t');
-        } else if (c == 'r') {
-          s.push_back('\r');
-        } else {
-          tokens.push_back(new Invalid(line, "Invalid escape code"));
-          idx = i;
-          while (idx < file_contents.size() &&
-                 (file_contents[idx] != '\n' || file_contents[idx] != '"'))
-            idx++;
-          idx += 1;
-          return;
+      } else if (c == 't') {
+        s.push_back('\t');
+      } else if (c == 'r') {
+        s.push_back('\r');
+      } else if (c == 'e' && options.legacy_escapes) {
+        s.push_back('\x1b');
+        warnings.push_back(Warning(cursor.line, "Deprecated escape code \\e"));
+      } else if (c == 'u' && !bytes) {
+        size_t end;
+        long code;
+        if (scanUnicode(cursor.offset, end, code).empty()) {
+          s += utf8(code);
+        } else if (!bad_escape) {
+          bad_escape = cursor.line;
         }
-      } else {
-        s.push_back(c);
+        cursor.advanceTo(end);
+        continue;
+      } else if (c == 'x') {
+        size_t end;
+        long code;
+        if (scanHex(cursor.offset, end, code).empty()) {
+          s.push_back(code);
+        } else if (!bad_escape) {
+          bad_escape = cursor.line;
+        }
+        cursor.advanceTo(end);
+        continue;
+      } else if (!bad_escape) {
+        bad_escape = cursor.line;
       }
+      cursor.advance();
+    }
+    if (cursor.done()) {
+      tokens.push_back(
+          unclosed(LexErrorKind::UnterminatedString, "Unterminated string",
+                   start));
+    } else if (bad_escape) {
+      tokens.push_back(new Invalid(bad_escape, LexErrorKind::InvalidEscape,
+                                   "Invalid escape code"));
+    } else if (bad_utf8) {
+      tokens.push_back(new Invalid(bad_utf8, LexErrorKind::InvalidUtf8,
+                                   "Invalid UTF-8 in string"));
+    } else if (non_ascii) {
+      tokens.push_back(new Invalid(non_ascii, LexErrorKind::InvalidChar,
+                                   "Non-ascii byte in byte string"));
+    } else {
+      if (options.max_string_length && s.size() > options.max_string_length) {
+        warnings.push_back(Warning(
+            l, "String literal longer than " +
+                   to_string(options.max_string_length) + " bytes"));
+      }
+      tokens.push_back(
+          new Literal(l, bytes ? LiteralType::Bytes : LiteralType::String, s));
+    }
+    cursor.advance();
+  }
+
+  bool atRawString() {
+    size_t quote = file_contents.find_first_not_of('#', cursor.offset + 1);
+    return quote != string::npos && file_contents[quote] == '"';
+  }
+
+  void lexRawString() {
+    auto l = cursor.line;
+    size_t start = cursor.offset;
+    size_t quote = file_contents.find_first_not_of('#', cursor.offset + 1);
+    string closing = "\"" + string(quote - cursor.offset - 1, '#');
+    size_t end = file_contents.find(closing, quote + 1);
+    if (end == string::npos) {
+      tokens.push_back(unclosed(
+          LexErrorKind::UnterminatedString,
+          "Unterminated raw string, expected `" + closing + "`", start));
+      cursor.advanceTo(file_contents.size());
+      return;
+    }
+    tokens.push_back(new Literal(
+        l, LiteralType::String,
+        payloads ? file_contents.substr(quote + 1, end - quote - 1) : ""));
+    cursor.advanceTo(end + closing.size());
+  }
+
+  string scanUnicode(size_t at, size_t &end, long &code) {
+    end = at + 1;
+    code = 0;
+    if (end >= file_contents.size() || file_contents[end] != '{') {
+      return "Expected { after \\u";
     }
-    if (file_contents[i] == '"') {
-      tokens.push_back(new Literal(l, s));
+    end++;
+    while (end < file_contents.size() && isxdigit(file_contents[end]) &&
+           end - at - 2 < 6) {
+      code = code * 16 + stoi(string(1, file_contents[end]), nullptr, 16);
+      end++;
+    }
+    if (end >= file_contents.size() || file_contents[end] != '}') {
+      return "Unterminated unicode escape";
+    }
+    end++;
+    if (end == at + 3 || code > 0x10FFFF ||
+        (code >= 0xD800 && code <= 0xDFFF)) {
+      return "Invalid unicode code point";
+    }
+    return "";
+  }
+
+  string scanHex(size_t at, size_t &end, long &code) {
+    end = at + 1;
+    code = 0;
+    while (end < file_contents.size() && isxdigit(file_contents[end]) &&
+           end - at - 1 < 2) {
+      code = code * 16 + stoi(string(1, file_contents[end]), nullptr, 16);
+      end++;
+    }
+    if (end - at - 1 < 2) {
+      return "Expected two hex digits after \\x";
+    }
+    return "";
+  }
+
+  static string utf8(long code) {
+    string s;
+    if (code < 0x80) {
+      s.push_back(code);
+    } else if (code < 0x800) {
+      s.push_back(0xC0 | (code >> 6));
+      s.push_back(0x80 | (code & 0x3F));
+    } else if (code < 0x10000) {
+      s.push_back(0xE0 | (code >> 12));
+      s.push_back(0x80 | ((code >> 6) & 0x3F));
+      s.push_back(0x80 | (code & 0x3F));
     } else {
-      tokens.push_back(new Invalid(l, "Unterminated string"));
+      s.push_back(0xF0 | (code >> 18));
+      s.push_back(0x80 | ((code >> 12) & 0x3F));
+      s.push_back(0x80 | ((code >> 6) & 0x3F));
+      s.push_back(0x80 | (code & 0x3F));
     }
-    idx = i + 1;
+    return s;
   }
 
   void lexChar() {
-    if (idx + 2 < file_contents.size()) {
-      char ch = file_contents[idx + 1];
+    if (cursor.offset + 2 < file_contents.size()) {
+      char ch = file_contents[cursor.offset + 1];
       if ((ch >= 'a' && ch <= 'z') || (ch >= 'A' && ch <= 'Z') || ch == '_' ||
           (ch >= '0' && ch <= '9') || ch == ' ') {
-        if (file_contents[idx + 2] == '\'') {
-          tokens.push_back(new Literal(line, file_contents.substr(idx, 3)));
-          idx += 3;
+        if (file_contents[cursor.offset + 2] == '\'') {
+          tokens.push_back(new Literal(cursor.line, LiteralType::Char,
+                                       file_contents.substr(cursor.offset, 3)));
+          cursor.advance(3);
         } else {
-          tokens.push_back(new Invalid(line, "Unterminated character literal"));
+          tokens.push_back(new Invalid(cursor.line,
+                                       LexErrorKind::UnterminatedChar,
+                                       "Unterminated character literal"));
+          cursor.advance(2);
+        }
+      } else if (ch == '\\' && (file_contents[cursor.offset + 2] == 'u' ||
+                                file_contents[cursor.offset + 2] == 'x')) {
+        size_t end;
+        long code;
+        string error = file_contents[cursor.offset + 2] == 'u'
+                           ? scanUnicode(cursor.offset + 2, end, code)
+                           : scanHex(cursor.offset + 2, end, code);
+        if (error.empty() && file_contents[cursor.offset + 2] == 'x' &&
+            code > 0x7F) {
+          error = "Hex escape outside ascii";
         }
-      } else if (ch == '\\' && idx + 3 < file_contents.size()) {
-        ch = file_contents[idx + 2];
-        if (file_contents[idx + 3] == '\'') {
+        if (error.empty() && end < file_contents.size() &&
+            file_contents[end] == '\'') {
+          tokens.push_back(new Literal(
+              cursor.line, LiteralType::Char,
+              file_contents.substr(cursor.offset, end + 1 - cursor.offset)));
+          end++;
+        } else {
+          tokens.push_back(
+              error.empty()
+                  ? new Invalid(cursor.line, LexErrorKind::UnterminatedChar,
+                                "Unterminated character literal")
+                  : new Invalid(cursor.line, LexErrorKind::InvalidEscape,
+                                error));
+          size_t close = file_contents.find_first_of("'\n", end);
+          if (close != string::npos && file_contents[close] == '\'') {
+            end = close + 1;
+          }
+        }
+        cursor.advanceTo(end);
+      } else if (ch == '\\' && cursor.offset + 3 < file_contents.size()) {
+        ch = file_contents[cursor.offset + 2];
+        if (file_contents[cursor.offset + 3] == '\'') {
           switch (ch) {
             case 'n':
             case 'r':
             case 't':
-              tokens.push_back(new Literal(line, file_contents.substr(idx, 4)));
-              idx += 4;
+              tokens.push_back(
+                  new Literal(cursor.line, LiteralType::Char,
+                              file_contents.substr(cursor.offset, 4)));
               break;
             default:
-              tokens.push_back(new Invalid(line, "Unknown escape code"));
+              tokens.push_back(new Invalid(cursor.line,
+                                           LexErrorKind::InvalidEscape,
+                                           "Unknown escape code"));
           }
+          cursor.advance(4);
         } else {
-          tokens.push_back(new Invalid(line, "Unterminated character literal"));
+          tokens.push_back(new Invalid(cursor.line,
+                                       LexErrorKind::UnterminatedChar,
+                                       "Unterminated character literal"));
+          cursor.advance(3);
         }
+      } else if (ch == '\\') {
+        tokens.push_back(
+            unclosed(LexErrorKind::UnterminatedChar,
+                                  "Unterminated character literal",
+                                  cursor.offset));
+        cursor.advanceTo(file_contents.size());
       } else {
-        tokens.push_back(new Invalid(line, "Charcater outside normal ascii"));
+        tokens.push_back(
+            new Invalid(cursor.line, LexErrorKind::InvalidChar,
+                        "Charcater outside normal ascii"));
+        cursor.advance(file_contents[cursor.offset + 2] == '\'' ? 3 : 2);
       }
     } else {
-      tokens.push_back(new Invalid(line, "Unterminated character literal"));
+      tokens.push_back(
+          unclosed(LexErrorKind::UnterminatedChar,
+                                  "Unterminated character literal",
+                                  cursor.offset));
+      cursor.advanceTo(file_contents.size());
     }
   }
 
-  void lexSymbol() {
-    auto search = [this](string s) {
-      if (auto it = SYMBOLS.find(s); it != SYMBOLS.end()) {
-        tokens.push_back(new Symbol(line, it->second));
-        idx += s.size();
+  void lexByteChar() {
+    size_t end = cursor.offset + 2;
+    string error;
+    auto error_kind = LexErrorKind::InvalidEscape;
+    if (end < file_contents.size() && file_contents[end] == '\\') {
+      long code;
+      char c = end + 1 < file_contents.size() ? file_contents[end + 1] : '\0';
+      if (c == 'x') {
+        error = scanHex(end + 1, end, code);
+      } else if (c == 'n' || c == 'r' || c == 't' || c == '\\' || c == '\'') {
+        end += 2;
+      } else {
+        error = "Unknown escape code";
+        end++;
+      }
+    } else if (end < file_contents.size() &&
+               (unsigned char)file_contents[end] > 0x7F) {
+      error = "Non-ascii byte in byte literal";
+      error_kind = LexErrorKind::InvalidChar;
+    } else if (end < file_contents.size() && file_contents[end] != '\'' &&
+               file_contents[end] != '\n') {
+      end++;
+    } else {
+      error = "Empty byte literal";
+      error_kind = LexErrorKind::InvalidChar;
+    }
+    if (error.empty() &&
+        (end >= file_contents.size() || file_contents[end] != '\'')) {
+      error = "Unterminated byte literal";
+      error_kind = LexErrorKind::UnterminatedChar;
+    }
+    if (error.empty()) {
+      tokens.push_back(
+          new Literal(cursor.line, LiteralType::Byte,
+                      file_contents.substr(cursor.offset,
+                                           end + 1 - cursor.offset)));
+      cursor.advanceTo(end + 1);
+      return;
+    }
+    tokens.push_back(new Invalid(cursor.line, error_kind, error));
+    size_t close = file_contents.find_first_of("'\n", end);
+    cursor.advanceTo(close != string::npos && file_contents[close] == '\''
+                         ? close + 1
+                         : min(end, file_contents.size()));
+  }
+
+  string directiveName() {
+    if (cursor.offset > 0) {
+      size_t before = file_contents.find_last_not_of(" \t", cursor.offset - 1);
+      if (before != string::npos && file_contents[before] != '\n') {
+        return "";
+      }
+    }
+    size_t end = cursor.offset + 1;
+    while (end < file_contents.size() && file_contents[end] >= 'a' &&
+           file_contents[end] <= 'z') {
+      end++;
+    }
+    string name =
+        file_contents.substr(cursor.offset + 1, end - cursor.offset - 1);
+    return DIRECTIVES.count(name) ? name : "";
+  }
+
+  void lexDirective() {
+    string name = directiveName();
+    size_t name_end = cursor.offset + 1 + name.size();
+    size_t end = min(file_contents.find('\n', name_end), file_contents.size());
+    size_t args_start = file_contents.find_first_not_of(" \t", name_end);
+    string args;
+    if (args_start < end) {
+      args = file_contents.substr(args_start, end - args_start);
+      args.erase(args.find_last_not_of(" \t\r") + 1);
+    }
+    tokens.push_back(new Directive(cursor.line, name, args));
+    cursor.advanceTo(end);
+  }
+
+  bool startsWith(const string &s) {
+    return !s.empty() && file_contents.compare(cursor.offset, s.size(), s) == 0;
+  }
+
+  bool atComment() {
+    for (auto &prefix : options.line_comment_prefixes) {
+      if (startsWith(prefix)) {
         return true;
+      }
+    }
+    return startsWith(options.block_comment_delims.first);
+  }
+
+  void lexComment() {
+    auto &[open, close] = options.block_comment_delims;
+    int l = cursor.line, depth = 0;
+    size_t start = cursor.offset, line_start = cursor.line_start;
+    bool block = startsWith(open);
+    size_t end;
+    if (block) {
+      depth = 1;
+      end = cursor.offset + open.size();
+      while (depth > 0 && end < file_contents.size()) {
+        if (file_contents.compare(end, close.size(), close) == 0) {
+          depth--;
+          end += close.size();
+        } else if (file_contents.compare(end, open.size(), open) == 0) {
+          depth++;
+          end += open.size();
+        } else {
+          end++;
+        }
+      }
+      if (depth > 0) {
+        cursor.advanceTo(end);
+        string levels = depth == 1 ? " level open)" : " levels open)";
+        tokens.push_back(unclosed(
+            LexErrorKind::UnterminatedComment,
+            "Unterminated block comment (" + to_string(depth) + levels,
+            start));
+      }
+    } else {
+      end = min(file_contents.find('\n', cursor.offset), file_contents.size());
+    }
+    // a bidi override in a comment can still reorder the code around it
+    int line = l;
+    for (size_t i = start; i < end && !ascii; i++) {
+      if (file_contents[i] == '\n') {
+        line++;
+        line_start = i + 1;
+      } else if (long code = invisibleAt(i)) {
+        // placed at the character, so step leaves its span alone
+        if (auto invalid = reportInvisible(line, code)) {
+          invalid->offset = i;
+          invalid->col = i - line_start + 1;
+          invalid->length = 3;
+        }
+        i += 2;
+      }
+    }
+    string text = file_contents.substr(start, end - start);
+    if (options.doc_comments && block && depth == 0 && text.size() > 4 &&
+        text.compare(0, 3, "/**") == 0 && text[3] != '*') {
+      tokens.push_back(new DocComment(l, text.substr(3, text.size() - 5)));
+    } else if (options.doc_comments && !block &&
+               text.compare(0, 3, "///") == 0 && text[3] != '/') {
+      tokens.push_back(new DocComment(l, text.substr(3)));
+    } else {
+      comments.push_back({start, end});
+    }
+    cursor.advanceTo(end);
+  }
+
+  void lexAttribute() {
+    int l = cursor.line;
+    size_t start = cursor.offset;
+    size_t first = tokens.size();
+    int depth = 0;
+    cursor.advance();
+    do {
+      size_t count = tokens.size();
+      step();
+      if (tokens.size() > count) {
+        auto symbol = dynamic_cast<Symbol *>(tokens.back());
+        if (symbol && symbol->value() == "[") {
+          depth++;
+        } else if (symbol && symbol->value() == "]") {
+          depth--;
+        }
+      }
+    } while (depth > 0 && cursor.offset < file_contents.size());
+    if (depth > 0) {
+      tokens.resize(first);
+      tokens.push_back(unclosed(LexErrorKind::UnterminatedAttribute,
+                                "Unterminated attribute", start));
+      return;
+    }
+    vector<Token *> inner(tokens.begin() + first + 1, tokens.end() - 1);
+    tokens.resize(first);
+    tokens.push_back(new Attribute(l, inner));
+  }
+
+  bool lexHole(TemplateString *tmpl) {
+    size_t first = tokens.size(), start = cursor.offset;
+    int depth = 1;
+    cursor.advance(2);
+    while (depth > 0 && cursor.offset < file_contents.size()) {
+      if (depth == 1 && (peek() == '`' || peek() == '\n')) {
+        break;
+      }
+      size_t count = tokens.size();
+      step();
+      if (tokens.size() > count) {
+        auto symbol = dynamic_cast<Symbol *>(tokens.back());
+        if (symbol && symbol->value() == "{") {
+          depth++;
+        } else if (symbol && symbol->value() == "}") {
+          depth--;
+        }
+      }
+    }
+    if (depth > 0) {
+      tokens.resize(first);
+      tokens.push_back(unclosed(LexErrorKind::UnterminatedHole,
+                                "Unterminated template hole", start));
+      return false;
+    }
+    tmpl->holes.push_back(vector<Token *>(tokens.begin() + first,
+                                          tokens.end() - 1));
+    tmpl->texts.push_back("");
+    tokens.resize(first);
+    return true;
+  }
+
+  void lexTemplate() {
+    size_t start = cursor.offset;
+    auto tmpl = new TemplateString(cursor.line);
+    tmpl->texts.push_back("");
+    cursor.advance();
+    while (cursor.offset < file_contents.size() && peek() != '`') {
+      if (peek() == '\\' && cursor.offset + 1 < file_contents.size()) {
+        char c = peek_next();
+        tmpl->texts.back() += c == 'n' ? '\n' : c == 't' ? '\t' : c;
+        cursor.advance(2);
+      } else if (peek() == '$' && peek_next() == '{') {
+        if (!lexHole(tmpl)) {
+          if (peek() == '`') {
+            cursor.advance();
+          }
+          return;
+        }
       } else {
+        tmpl->texts.back() += peek();
+        cursor.advance();
+      }
+    }
+    if (cursor.offset >= file_contents.size()) {
+      tokens.push_back(unclosed(LexErrorKind::UnterminatedTemplate,
+                                "Unterminated template string", start));
+      cursor.advanceTo(file_contents.size());
+      return;
+    }
+    cursor.advance();
+    tokens.push_back(tmpl);
+  }
+
+  static bool symbolEnabled(const string &spelling,
+                            const LexerOptions &options) {
+    return (options.elementwise_operators ||
+            !ELEMENTWISE_OPERATORS.count(spelling)) &&
+           (options.backslash_symbol || spelling != "\\");
+  }
+
+  bool matchSymbol(size_t len) {
+    if (cursor.offset + len > file_contents.size()) {
+      return false;
+    }
+    string spelling = file_contents.substr(cursor.offset, len);
+    if (!symbolEnabled(spelling, options)) {
+      return false;
+    }
+    if (auto it = SYMBOLS.find(spelling); it != SYMBOLS.end()) {
+      tokens.push_back(new Symbol(cursor.line, it->second));
+      cursor.advance(len);
+      return true;
+    }
+    return false;
+  }
+
+  void registerLiteral(
+      function<Token *(const string &, size_t, size_t &)> literal_lexer) {
+    literal_lexers.push_back(literal_lexer);
+  }
+
+  bool lexCustomLiteral() {
+    for (auto &literal_lexer : literal_lexers) {
+      size_t length = 0;
+      Token *token = literal_lexer(file_contents, cursor.offset, length);
+      if (token && length > 0) {
+        token->line = cursor.line;
+        tokens.push_back(token);
+        cursor.advance(length);
+        return true;
+      }
+    }
+    return false;
+  }
+
+  bool registerOperator(string op) {
+    if (op.empty() || op.size() > options.max_operator_length ||
+        SYMBOLS.count(op)) {
+      return false;
+    }
+    for (char c : op) {
+      auto cls = classifyByte(c);
+      if (cls != ByteClass::SymbolStart && cls != ByteClass::Other) {
         return false;
       }
-    };
-    if (idx + 1 < file_contents.size()) {
-      auto sym = file_contents.substr(idx, 2);
-      if (!search(sym)) {
-        auto sym = file_contents.substr(idx, 1);
-        if (!search(sym)) {
-          tokens.push_back(new Invalid(line, "Unknown Symbol"));
-          idx += 1;
+    }
+    custom_operators.insert(op);
+    return true;
+  }
+
+  void lexSymbol() {
+    for (size_t len = max(options.max_operator_length, MAX_SYMBOL_LENGTH);
+         len > 0; len--) {
+      if (cursor.offset + len <= file_contents.size() &&
+          custom_operators.count(file_contents.substr(cursor.offset, len))) {
+        tokens.push_back(
+            new Symbol(cursor.line, file_contents.substr(cursor.offset, len)));
+        cursor.advance(len);
+        return;
+      }
+      if (len <= MAX_SYMBOL_LENGTH && matchSymbol(len)) {
+        return;
+      }
+    }
+    if (lexCustomLiteral()) {
+      return;
+    }
+    // a backslash ending the input may be a line continuation
+    bool continued =
+        peek() == '\\' && cursor.offset + 1 == file_contents.size();
+    auto invalid = new Invalid(cursor.line, LexErrorKind::UnexpectedChar,
+                               peek() == '\\' ? "Unexpected backslash"
+                                               : "Unknown Symbol",
+                               continued);
+    tokens.push_back(invalid);
+    size_t end = cursor.offset + max<size_t>(1, utf8Length(cursor.offset));
+    if (options.group_unknown) {
+      while (end < file_contents.size() &&
+             classifyByte(file_contents[end]) == ByteClass::Other) {
+        end++;
+      }
+    }
+    invalid->text = file_contents.substr(cursor.offset, end - cursor.offset);
+    cursor.advanceTo(end);
+  }
+
+  // cursor points at this lexer's file_contents, so a copy or move would
+  // read the original's source; declaring these also removes the moves
+  Lexer(const Lexer &) = delete;
+  Lexer &operator=(const Lexer &) = delete;
+
+  Lexer(string contents, LexerOptions options = LexerOptions()) {
+    file_contents = contents;
+    this->options = options;
+    ascii = none_of(contents.begin(), contents.end(),
+                    [](char c) { return (unsigned char)c > 0x7F; });
+    tokens.reserve(options.token_capacity ? options.token_capacity
+                                          : contents.size() / 4);
+  }
+
+  size_t offsetOf(int line, int col) {
+    size_t offset = 0;
+    for (int l = 1; l < line; l++) {
+      offset = file_contents.find('\n', offset);
+      if (offset == string::npos) {
+        return string::npos;
+      }
+      offset += 1;
+    }
+    size_t line_end =
+        min(file_contents.find('\n', offset), file_contents.size());
+    if (line < 1 || col < 1 || offset + col - 1 > line_end) {
+      return string::npos;
+    }
+    return offset + col - 1;
+  }
+
+  static Position endPosition(const string &input) {
+    size_t last = input.rfind('\n');
+    int col = last == string::npos ? input.size() : input.size() - last - 1;
+    return {1 + (int)count(input.begin(), input.end(), '\n'), col + 1};
+  }
+
+  IndentStyle detectIndent() {
+    bool tabs = false, spaces = false;
+    size_t width = 0;
+    for (size_t start = 0; start < file_contents.size();) {
+      size_t end = min(file_contents.find('\n', start), file_contents.size());
+      size_t text = file_contents.find_first_not_of(" \t", start);
+      if (text < end) {
+        string indent = file_contents.substr(start, text - start);
+        if (indent.find('\t') != string::npos) {
+          tabs = true;
+        }
+        if (indent.find(' ') != string::npos) {
+          spaces = true;
+          width = gcd(width, indent.size());
         }
       }
+      start = end + 1;
+    }
+    if (tabs && spaces) {
+      return {IndentKind::Mixed, 0};
+    } else if (tabs) {
+      return {IndentKind::Tabs, 0};
+    } else if (spaces) {
+      return {IndentKind::Spaces, width};
+    }
+    return {IndentKind::None, 0};
+  }
+
+  vector<Token *> tokensInLines(int start, int end) {
+    auto first = lower_bound(
+        tokens.begin(), tokens.end(), start,
+        [](Token *token, int line) { return token->line < line; });
+    auto last = upper_bound(
+        first, tokens.end(), end,
+        [](int line, Token *token) { return line < token->line; });
+    return vector<Token *>(first, last);
+  }
+
+  static vector<pair<int, vector<Token *>>> linesWithTokens(
+      string source, LexerOptions options = LexerOptions()) {
+    Lexer lexer(source, options);
+    lexer.lex();
+    vector<pair<int, vector<Token *>>> lines;
+    for (auto token : lexer.tokens) {
+      if (lines.empty() || lines.back().first != token->line) {
+        lines.push_back({token->line, {}});
+      }
+      lines.back().second.push_back(token);
+    }
+    return lines;
+  }
+
+  static vector<size_t> tokensPerLine(const vector<Token *> &tokens) {
+    vector<size_t> counts;
+    for (auto token : tokens) {
+      if ((size_t)token->line > counts.size()) {
+        counts.resize(token->line);
+      }
+      counts[token->line - 1]++;
+    }
+    return counts;
+  }
+
+  static size_t maxLineLengthInTokens(const vector<Token *> &tokens) {
+    auto counts = tokensPerLine(tokens);
+    return counts.empty() ? 0 : *max_element(counts.begin(), counts.end());
+  }
+
+  static vector<size_t> chainBoundaries(const vector<Token *> &tokens) {
+    vector<size_t> boundaries;
+    for (size_t i = 1; i < tokens.size(); i++) {
+      auto symbol = dynamic_cast<Symbol *>(tokens[i]);
+      if (symbol && symbol->value() == "." &&
+          tokens[i]->line > tokens[i - 1]->line) {
+        boundaries.push_back(i);
+      }
+    }
+    return boundaries;
+  }
+
+  static set<string> distinctSymbols(const vector<Token *> &tokens) {
+    set<string> symbols;
+    for (auto token : tokens) {
+      if (auto symbol = dynamic_cast<Symbol *>(token)) {
+        symbols.insert(symbol->value());
+      }
+    }
+    return symbols;
+  }
+
+  static set<string> distinctKeywords(const vector<Token *> &tokens) {
+    set<string> keywords;
+    for (auto token : tokens) {
+      if (auto keyword = dynamic_cast<Keyword *>(token)) {
+        keywords.insert(REVERSE_KEYWORD[keyword->id]);
+      }
+    }
+    return keywords;
+  }
+
+  // replaces the entry rather than editing the token, so a parser splitting
+  // its copy of the stream leaves the lexer's tokens as they were
+  static bool splitSymbol(vector<Token *> &tokens, size_t at, string doubled) {
+    auto symbol = at < tokens.size() ? dynamic_cast<Symbol *>(tokens[at])
+                                     : nullptr;
+    if (!symbol || symbol->id != SYMBOLS.at(doubled)) {
+      return false;
+    }
+    int id = SYMBOLS.at(doubled.substr(1));
+    auto first = new Symbol(symbol->line, id);
+    auto second = new Symbol(symbol->line, id);
+    first->offset = symbol->offset;
+    first->col = symbol->col;
+    second->offset = symbol->offset + 1;
+    second->col = symbol->col + 1;
+    first->length = second->length = 1;
+    tokens[at] = first;
+    tokens.insert(tokens.begin() + at + 1, second);
+    return true;
+  }
+
+  bool splitShift(size_t at) { return splitSymbol(tokens, at, ">>"); }
+
+  static ByteClass classifyByte(char b) {
+    if ((b >= 'a' && b <= 'z') || (b >= 'A' && b <= 'Z') || b == '_') {
+      return ByteClass::IdentStart;
+    } else if (b >= '0' && b <= '9') {
+      return ByteClass::Digit;
+    } else if (b == '"') {
+      return ByteClass::StringQuote;
+    } else if (b == '\'') {
+      return ByteClass::CharQuote;
+    } else if (b == '\n') {
+      return ByteClass::Newline;
+    } else if (b == ' ' || b == '\t' || b == '\r') {
+      return ByteClass::Whitespace;
+    } else if (b == '#') {
+      return ByteClass::Hash;
+    } else if (SYMBOLS.count(string(1, b))) {
+      return ByteClass::SymbolStart;
+    }
+    return ByteClass::Other;
+  }
+
+  bool afterOperand() {
+    if (cursor.offset == 0) {
+      return false;
+    }
+    char c = file_contents[cursor.offset - 1];
+    return isalnum(c) || c == '_' || c == ')' || c == ']';
+  }
+
+  long invisibleAt(size_t at) {
+    if (at + 2 >= file_contents.size()) {
+      return 0;
+    }
+    auto byte = [this, at](size_t i) {
+      return (unsigned char)file_contents[at + i];
+    };
+    if (byte(0) == 0xEF && byte(1) == 0xBB && byte(2) == 0xBF) {
+      return 0xFEFF;
+    } else if (byte(0) != 0xE2 || (byte(1) & 0xFE) != 0x80 ||
+               (byte(2) & 0xC0) != 0x80) {
+      return 0;
+    }
+    long code = 0x2000 | ((byte(1) & 0x3F) << 6) | (byte(2) & 0x3F);
+    if ((code >= 0x200B && code <= 0x200F) ||
+        (code >= 0x202A && code <= 0x202E) ||
+        (code >= 0x2060 && code <= 0x2069)) {
+      return code;
+    }
+    return 0;
+  }
+
+  size_t utf8Length(size_t at) {
+    auto lead = (unsigned char)file_contents[at];
+    size_t length = lead < 0x80           ? 1
+                    : (lead & 0xE0) == 0xC0 ? 2
+                    : (lead & 0xF0) == 0xE0 ? 3
+                    : (lead & 0xF8) == 0xF0 ? 4
+                                            : 0;
+    if (!length || at + length > file_contents.size() || lead == 0xC0 ||
+        lead == 0xC1 || lead > 0xF4) {
+      return 0;
+    }
+    for (size_t i = 1; i < length; i++) {
+      if ((file_contents[at + i] & 0xC0) != 0x80) {
+        return 0;
+      }
+    }
+    return length;
+  }
+
+  void lexInvalidUtf8() {
+    stringstream details;
+    details << "Invalid UTF-8 byte 0x" << hex << uppercase
+            << (int)(unsigned char)peek() << " at offset " << dec
+            << cursor.offset;
+    tokens.push_back(
+        new Invalid(cursor.line, LexErrorKind::InvalidUtf8, details.str()));
+    cursor.advance();
+  }
+
+  Invalid *reportInvisible(int line, long code) {
+    stringstream name;
+    name << "Invisible character U+" << hex << uppercase << code;
+    if (options.invisible_chars == InvisiblePolicy::Reject) {
+      auto invalid = new Invalid(line, LexErrorKind::InvalidChar, name.str());
+      tokens.push_back(invalid);
+      return invalid;
+    } else if (options.invisible_chars == InvisiblePolicy::Warn) {
+      warnings.push_back(Warning(line, name.str()));
+    }
+    return nullptr;
+  }
+
+  void lexInvisible(long code) {
+    reportInvisible(cursor.line, code);
+    cursor.advance(3);
+  }
+
+  void step() {
+    size_t start = cursor.offset, count = tokens.size();
+    int col = cursor.col();
+    ByteClass cls = classifyByte(peek());
+    if (!ascii && (unsigned char)peek() > 0x7F &&
+        !utf8Length(cursor.offset)) {
+      lexInvalidUtf8();
+    } else if (long code = !ascii && cls == ByteClass::Other
+                               ? invisibleAt(cursor.offset)
+                               : 0) {
+      lexInvisible(code);
+    } else if (peek() == 'r' && atRawString()) {
+      lexRawString();
+    } else if (peek() == 'b' && peek_next() == '"') {
+      lexString(true);
+    } else if (peek() == 'b' && peek_next() == '\'') {
+      lexByteChar();
+    } else if (cls == ByteClass::IdentStart) {
+      lexWord();
+    } else if (cls == ByteClass::Digit ||
+               (peek() == '.' && isdigit(peek_next()) && !afterOperand())) {
+      lexNumber();
+    } else if (cls == ByteClass::StringQuote) {
+      lexString();
+    } else if (cls == ByteClass::CharQuote) {
+      lexChar();
+    } else if (cls == ByteClass::Newline || cls == ByteClass::Whitespace) {
+      cursor.advance();
+    } else if (cls == ByteClass::Hash && options.attributes &&
+               peek_next() == '[') {
+      lexAttribute();
+    } else if (cls == ByteClass::Hash && options.directives &&
+               !directiveName().empty()) {
+      lexDirective();
+    } else if (peek() == '`' && options.template_strings) {
+      lexTemplate();
+    } else if (atComment()) {
+      lexComment();
     } else {
-      auto sym = file_contents.substr(idx, 1);
-      if (!search(sym)) {
-        tokens.push_back(new Invalid(line, "Unknown Symbol"));
-        idx += 1;
+      lexSymbol();
+    }
+    for (size_t i = count; i < tokens.size(); i++) {
+      if (tokens[i]->length) {
+        continue;
+      }
+      // unclosed() already placed its token at the opener
+      auto invalid = dynamic_cast<Invalid *>(tokens[i]);
+      if (!invalid || !invalid->opener) {
+        tokens[i]->offset = start;
+        tokens[i]->col = col;
+      }
+      tokens[i]->length = cursor.offset - tokens[i]->offset;
+      if (invalid && invalid->incomplete && cursor.done()) {
+        // the input ran out mid-token, so more input may still complete it
+        invalid->error_kind = LexErrorKind::Incomplete;
       }
     }
   }
 
-  Lexer(string contents) {
-    file_contents = contents;
-    line = 1;
+  // lexAttribute and lexHole step through tokens they later fold away, so
+  // only lex() counts, once a top-level step has produced its tokens
+  void tally(size_t from) {
+    for (size_t i = from; i < tokens.size(); i++) {
+      stats.total++;
+      stats.kinds[tokens[i]->kind()]++;
+      stats.errors += tokens[i]->kind() == TokenKind::Invalid;
+    }
+    stats.bytes = cursor.offset;
   }
 
   void lex() {
-    while (idx < file_contents.size()) {
-      if ((peek() >= 'a' && peek() <= 'z') ||
-          (peek() >= 'A' && peek() <= 'Z') || (peek() == '_')) {
-        lexWord();
-      } else if (peek() >= '0' && peek() <= '9') {
-        lexNumber();
-      } else if (peek() == '"') {
-        lexString();
-      } else if (peek() == '\'') {
-        lexChar();
-      } else if (peek() == '\n') {
-        line += 1;
-        idx += 1;
-      } else if (peek() == ' ') {
-        idx += 1;
-      } else if (peek() == '#') {
-        line += 1;
-        while (idx < file_contents.size() && file_contents[idx++] != '\n')
-          ;
+    size_t next_progress = options.progress_interval;
+    while (cursor.offset < file_contents.size()) {
+      size_t before = cursor.offset, count = tokens.size();
+      step();
+      if (cursor.offset == before) {
+        cursor.advance();
+      }
+      if (options.recover_to_delimiter && tokens.size() > count) {
+        if (auto invalid = dynamic_cast<Invalid *>(tokens.back())) {
+          skipToDelimiter(invalid);
+        }
+      }
+      tally(count);
+      while (options.progress && next_progress > 0 &&
+             next_progress <= min(cursor.offset, file_contents.size())) {
+        options.progress(next_progress, file_contents.size());
+        next_progress += options.progress_interval;
+      }
+      if (options.stop_on_error && !tokens.empty() &&
+          dynamic_cast<Invalid *>(tokens.back())) {
+        break;
+      }
+    }
+    if (options.trivia) {
+      attachTrivia();
+    }
+    if (options.eof_token) {
+      Position end = endPosition(file_contents);
+      auto eof = new Eof(end.line);
+      eof->offset = file_contents.size();
+      eof->col = end.col;
+      tokens.push_back(eof);
+    }
+  }
+
+  void attachTrivia() {
+    Cursor at(&file_contents);
+    for (auto [start, end] : comments) {
+      at.seek(start);
+      string text = file_contents.substr(start, end - start);
+      auto next = upper_bound(
+          tokens.begin(), tokens.end(), start,
+          [](size_t offset, Token *token) { return offset < token->offset; });
+      Token *prev = next == tokens.begin() ? nullptr : *(next - 1);
+      if (prev && (prev->line == at.line ||
+                   next == tokens.end())) {
+        prev->trailing.push_back(text);
+      } else if (next != tokens.end()) {
+        (*next)->leading.push_back(text);
+      }
+    }
+  }
+
+  // frees each token once its kind is read and leaves stats alone, so a
+  // kind-only scan neither keeps the stream nor counts it
+  optional<TokenKind> nextKind() {
+    payloads = false;
+    while (tokens.empty() && !cursor.done()) {
+      size_t before = cursor.offset;
+      step();
+      if (cursor.offset == before) {
+        cursor.advance();
+      }
+      // handed out from the back, so put the first one last
+      reverse(tokens.begin(), tokens.end());
+    }
+    payloads = true;
+    if (tokens.empty()) {
+      return nullopt;
+    }
+    TokenKind kind = tokens.back()->kind();
+    delete tokens.back();
+    tokens.pop_back();
+    return kind;
+  }
+
+#ifdef LEXER_COMPACT_TOKENS
+  CompactTokens compact() {
+    CompactTokens compact;
+    for (auto token : tokens) {
+      compact.kinds.push_back((uint8_t)token->kind());
+      compact.offsets.push_back(token->offset);
+    }
+    return compact;
+  }
+
+  // restores against this lexer's source and options, and keeps its
+  // registered operators and literal lexers
+  vector<Token *> restore(CompactTokens &compact) {
+    vector<Token *> restored;
+    Lexer lexer(file_contents, options);
+    lexer.custom_operators = custom_operators;
+    lexer.literal_lexers = literal_lexers;
+    for (size_t i = 0; i < compact.offsets.size(); i++) {
+      lexer.tokens.clear();
+      lexer.cursor.seek(compact.offsets[i]);
+      lexer.step();
+      if (lexer.tokens.empty() ||
+          (uint8_t)lexer.tokens[0]->kind() != compact.kinds[i]) {
+        return {};
+      }
+      restored.push_back(lexer.tokens[0]);
+    }
+    return restored;
+  }
+
+  static vector<Token *> restore(CompactTokens &compact, string source,
+                                 LexerOptions options = LexerOptions()) {
+    return Lexer(source, options).restore(compact);
+  }
+#endif
+
+  static GrammarTables grammarTables(LexerOptions options = LexerOptions()) {
+    GrammarTables tables;
+    for (auto &keyword : REVERSE_KEYWORD) {
+      tables.keywords.push_back(keyword);
+    }
+    for (size_t id = 0; id < size(REVERSE_SYMBOL); id++) {
+      if (SYMBOLS.at(REVERSE_SYMBOL[id]) == (int)id &&
+          symbolEnabled(REVERSE_SYMBOL[id], options)) {
+        tables.symbols.push_back(REVERSE_SYMBOL[id]);
+      }
+    }
+    string sep;
+    if (options.digit_separator) {
+      sep = string(strchr("\\]^-", options.digit_separator) ? "\\" : "") +
+            options.digit_separator;
+    }
+    auto run = [&sep](string digits) { return "[" + digits + sep + "]*"; };
+    auto radix = [&sep, &run](string prefix, string digits) {
+      return prefix + (sep.empty() ? "" : "[" + sep + "]*") + "[" + digits +
+             "]" + run(digits);
+    };
+    vector<string> sorted(INT_SUFFIXES.begin(), INT_SUFFIXES.end());
+    sort(sorted.begin(), sorted.end());
+    string int_suffix;
+    for (auto &suffix : sorted) {
+      int_suffix += (int_suffix.empty() ? "" : "|") + suffix;
+    }
+    string dec = "[0-9]" + run("0-9"), exponent = "[eE][+-]?" + dec;
+    string float_pattern = "(" + dec + "(\\." + dec + ")?" + exponent + "|" +
+                           dec + "\\." + dec + "|\\." + dec + "(" +
+                           exponent + ")?|" + dec +
+                           "(?=f32|f64))(f32|f64)?|" + dec +
+                           "\\.(?![.A-Za-z_])";
+    if (options.float_words) {
+      float_pattern += "|inf|nan";
+    }
+    // numbers_as_raw keeps both shapes but lexes them as Number
+    bool raw = options.numbers_as_raw;
+    tables.literal_patterns = {
+        {raw ? "Number" : "Int",
+         "(" + radix("0[xX]", "0-9a-fA-F") + "|" + radix("0[bB]", "01") + "|" +
+             radix("0[oO]", "0-7") + "|" + dec + ")(" + int_suffix + ")?"},
+        {raw ? "Number" : "Float", float_pattern},
+        {"Bool", "true|false"},
+        {"String", "\"(\\\\u\\{[0-9a-fA-F]{1,6}\\}|\\\\.|[^\"\\\\])*\""},
+        {"String", "r(#*)\"[\\s\\S]*?\"\\1"},
+        {"Bytes", "b\"(\\\\.|[\\x00-\\x21\\x23-\\x5B\\x5D-\\x7F])*\""},
+        {"Byte",
+         "b'(\\\\[nrt\\\\']|\\\\x[0-9a-fA-F]{2}|"
+         "[\\x00-\\x09\\x0B-\\x26\\x28-\\x5B\\x5D-\\x7F])'"},
+        {"Char",
+         "'(\\\\[nrt]|\\\\x[0-7][0-9a-fA-F]|\\\\u\\{[0-9a-fA-F]{1,6}\\}|"
+         "[A-Za-z0-9_ ])'"},
+        {"Null", options.null_word}};
+    if (options.template_strings) {
+      tables.literal_patterns.push_back(
+          {"Template", "`(\\\\.|\\$\\{[^}]*\\}|[^`\\\\])*`"});
+    }
+    return tables;
+  }
+
+  static string stripComments(string source,
+                              LexerOptions options = LexerOptions()) {
+    Lexer lexer(source, options);
+    lexer.lex();
+    for (auto &[start, end] : lexer.comments) {
+      for (size_t i = start; i < end; i++) {
+        if (source[i] != '\n') {
+          source[i] = ' ';
+        }
+      }
+    }
+    return source;
+  }
+
+  void skipToDelimiter(Invalid *invalid) {
+    while (!cursor.done() && !isspace(peek()) &&
+           string(";,()[]{}").find(peek()) == string::npos) {
+      cursor.advance();
+    }
+    invalid->length = cursor.offset - invalid->offset;
+    if (!invalid->text.empty()) {
+      invalid->text = file_contents.substr(invalid->offset, invalid->length);
+    }
+  }
+
+  pair<vector<Token *>, vector<Invalid *>> lexAll() {
+    bool recover = options.recover_to_delimiter;
+    options.recover_to_delimiter = true;
+    lex();
+    options.recover_to_delimiter = recover;
+    vector<Token *> valid;
+    vector<Invalid *> errors;
+    for (auto token : tokens) {
+      if (auto invalid = dynamic_cast<Invalid *>(token)) {
+        errors.push_back(invalid);
+      } else {
+        valid.push_back(token);
+      }
+    }
+    return {valid, errors};
+  }
+
+  static string escapeHtml(const string &text) {
+    string escaped;
+    for (char c : text) {
+      if (c == '&') {
+        escaped += "&amp;";
+      } else if (c == '<') {
+        escaped += "&lt;";
+      } else if (c == '>') {
+        escaped += "&gt;";
+      } else if (c == '"') {
+        escaped += "&quot;";
       } else {
-        lexSymbol();
+        escaped.push_back(c);
       }
     }
+    return escaped;
   }
+
+  static string highlightHtml(string source,
+                              LexerOptions options = LexerOptions()) {
+    const map<TokenKind, string> classes{
+        {TokenKind::Keyword, "kw"},    {TokenKind::Identifier, "id"},
+        {TokenKind::Literal, "lit"},   {TokenKind::Symbol, "sym"},
+        {TokenKind::Invalid, "err"},   {TokenKind::Directive, "dir"},
+        {TokenKind::Attribute, "attr"}, {TokenKind::Template, "lit"},
+        {TokenKind::DocComment, "doc"}};
+    Lexer lexer(source, options);
+    lexer.lex();
+    string html;
+    size_t at = 0;
+    for (auto token : lexer.tokens) {
+      if (token->offset < at || token->kind() == TokenKind::Eof) {
+        continue;
+      }
+      html += escapeHtml(source.substr(at, token->offset - at));
+      html += "<span class=\"" + classes.at(token->kind()) + "\">" +
+              escapeHtml(source.substr(token->offset, token->length)) +
+              "</span>";
+      at = token->offset + token->length;
+    }
+    return html + escapeHtml(source.substr(at));
+  }
+
+  bool isIncomplete() {
+    size_t last = tokens.size();
+    if (last && tokens[last - 1]->kind() == TokenKind::Eof) {
+      last--;
+    }
+    if (!last) {
+      return false;
+    }
+    Token *token = tokens[last - 1];
+    if (auto symbol = dynamic_cast<Symbol *>(token)) {
+      // `a +` may still become `a +=` or `a ++`
+      return token->offset + token->length == file_contents.size() &&
+             extendable(symbol->value());
+    }
+    auto invalid = dynamic_cast<Invalid *>(token);
+    return invalid && invalid->error_kind == LexErrorKind::Incomplete;
+  }
+
+  bool extendable(const string &spelling) {
+    auto longer = [&spelling](const string &op) {
+      return op.size() > spelling.size() &&
+             op.compare(0, spelling.size(), spelling) == 0;
+    };
+    for (auto &[op, id] : SYMBOLS) {
+      if (longer(op)) {
+        return true;
+      }
+    }
+    return any_of(custom_operators.begin(), custom_operators.end(), longer);
+  }
+
+  static Invalid *validate(string input,
+                           LexerOptions options = LexerOptions()) {
+    options.stop_on_error = true;
+    options.eof_token = false;
+    Lexer lexer(input, options);
+    lexer.lex();
+    if (lexer.tokens.empty()) {
+      return nullptr;
+    }
+    return dynamic_cast<Invalid *>(lexer.tokens.back());
+  }
+
+  static bool isValid(string input, LexerOptions options = LexerOptions()) {
+    return validate(input, options) == nullptr;
+  }
+};
+
+class TokenStreamBuilder {
+ public:
+  vector<Token *> tokens;
+
+  bool push(Token *token) {
+    if (!tokens.empty() &&
+        (token->offset <= tokens.back()->offset ||
+         token->offset < tokens.back()->offset + tokens.back()->length ||
+         token->line < tokens.back()->line)) {
+      return false;
+    }
+    tokens.push_back(token);
+    return true;
+  }
+
+  vector<Token *> build() { return tokens; }
 };
+
+#endif
@@ -5,15 +5,30 @@
 using namespace std;
 
 int main(int argc, char *argv[]) {
-  if (argc < 2) {
+  vector<string> files;
+  string span;
+  for (int i = 1; i < argc; i++) {
+    string arg = argv[i];
+    if (arg == "--span") {
+      if (i + 1 == argc) {
+        cerr << "Missing span, expected --span L1:C1-L2:C2" << endl;
+        return 3;
+      }
+      span = argv[++i];
+    } else {
+      files.push_back(arg);
+    }
+  }
+
+  if (files.empty()) {
     cerr << "No input files" << endl;
     return 1;
-  } else if (argc > 2) {
+  } else if (files.size() > 1) {
     cerr << "Multiple files not supported" << endl;
     return 2;
   }
 
-  ifstream codefile(argv[1]);
+  ifstream codefile(files[0]);
   string contents;
   string line;
   while (getline(codefile, line)) {
@@ -23,6 +38,22 @@ int main(int argc, char *argv[]) {
 
   Lexer lexer(contents);
 
+  if (!span.empty()) {
+    int l1, c1, l2, c2;
+    if (sscanf(span.c_str(), "%d:%d-%d:%d", &l1, &c1, &l2, &c2) != 4) {
+      cerr << "Invalid span " << span << ", expected L1:C1-L2:C2" << endl;
+      return 3;
+    }
+    size_t start = lexer.offsetOf(l1, c1);
+    size_t end = lexer.offsetOf(l2, c2);
+    if (start == string::npos || end == string::npos || start > end) {
+      cerr << "Span " << span << " is outside the file" << endl;
+      return 3;
+    }
+    cout << contents.substr(start, end - start) << endl;
+    return 0;
+  }
+
   lexer.lex();
   for (auto token: lexer.tokens) {
     cout << token->to_str() << endl;
@@ -0,0 +1,782 @@
+#ifndef PARSER_CPP
+#define PARSER_CPP
+
+#include <bits/stdc++.h>
+
+#include "lexer.cpp"
+
+using namespace std;
+
+const unordered_map<string, int> BINARY_PRECEDENCE{
+    {"||", 1}, {"&&", 2}, {"==", 3}, {"<", 4},  {">", 4},  {"<=", 4},
+    {">=", 4}, {"|", 5},  {"^", 6},  {"&", 7},  {"<<", 8}, {">>", 8},
+    {"+", 9},  {"-", 9},  {"*", 10}, {"/", 10}, {"%", 10},
+    {".*", 10}, {"./", 10}, {".^", 10}};
+
+class Expr {
+ public:
+  int line;
+  Expr() = delete;
+  Expr(int line) { this->line = line; }
+  virtual string to_str() { return "Expr {line: something}"; }
+};
+
+class LiteralExpr : public Expr {
+ public:
+  Literal *value;
+
+  LiteralExpr(Literal *value) : Expr(value->line) { this->value = value; }
+  string to_str() { return "Literal {value: " + value->value + "}"; }
+};
+
+class VariableExpr : public Expr {
+ public:
+  string name;
+
+  VariableExpr(int line, string name) : Expr(line) { this->name = name; }
+  string to_str() { return "Variable {name: \"" + name + "\"}"; }
+};
+
+class BinaryExpr : public Expr {
+ public:
+  string op;
+  Expr *lhs;
+  Expr *rhs;
+
+  BinaryExpr(int line, string op, Expr *lhs, Expr *rhs) : Expr(line) {
+    this->op = op;
+    this->lhs = lhs;
+    this->rhs = rhs;
+  }
+  string to_str() {
+    return "Binary {op: \"" + op + "\", lhs: " + lhs->to_str() +
+           ", rhs: " + rhs->to_str() + "}";
+  }
+};
+
+class UnaryExpr : public Expr {
+ public:
+  string op;
+  Expr *operand;
+
+  UnaryExpr(int line, string op, Expr *operand) : Expr(line) {
+    this->op = op;
+    this->operand = operand;
+  }
+  string to_str() {
+    return "Unary {op: \"" + op + "\", operand: " + operand->to_str() + "}";
+  }
+};
+
+class AssignExpr : public Expr {
+ public:
+  Expr *target;
+  Expr *value;
+
+  AssignExpr(int line, Expr *target, Expr *value) : Expr(line) {
+    this->target = target;
+    this->value = value;
+  }
+  string to_str() {
+    return "Assign {target: " + target->to_str() +
+           ", value: " + value->to_str() + "}";
+  }
+};
+
+class TernaryExpr : public Expr {
+ public:
+  Expr *cond;
+  Expr *then;
+  Expr *otherwise;
+
+  TernaryExpr(int line, Expr *cond, Expr *then, Expr *otherwise)
+      : Expr(line) {
+    this->cond = cond;
+    this->then = then;
+    this->otherwise = otherwise;
+  }
+  string to_str() {
+    return "Ternary {cond: " + cond->to_str() + ", then: " + then->to_str() +
+           ", else: " + otherwise->to_str() + "}";
+  }
+};
+
+class CallExpr : public Expr {
+ public:
+  Expr *callee;
+  vector<Expr *> args;
+
+  CallExpr(int line, Expr *callee, vector<Expr *> args) : Expr(line) {
+    this->callee = callee;
+    this->args = args;
+  }
+  string to_str() {
+    stringstream s;
+    s << "Call {callee: " << callee->to_str() << ", args: [";
+    for (size_t i = 0; i < args.size(); i++) {
+      s << (i ? ", " : "") << args[i]->to_str();
+    }
+    s << "]}";
+    return s.str();
+  }
+};
+
+class ArrayExpr : public Expr {
+ public:
+  vector<Expr *> items;
+
+  ArrayExpr(int line, vector<Expr *> items) : Expr(line) {
+    this->items = items;
+  }
+  string to_str() {
+    stringstream s;
+    s << "Array {items: [";
+    for (size_t i = 0; i < items.size(); i++) {
+      s << (i ? ", " : "") << items[i]->to_str();
+    }
+    s << "]}";
+    return s.str();
+  }
+};
+
+class RepeatExpr : public Expr {
+ public:
+  Expr *value;
+  Expr *count;
+
+  RepeatExpr(int line, Expr *value, Expr *count) : Expr(line) {
+    this->value = value;
+    this->count = count;
+  }
+  string to_str() {
+    return "Repeat {value: " + value->to_str() + ", count: " +
+           count->to_str() + "}";
+  }
+};
+
+class IndexExpr : public Expr {
+ public:
+  Expr *object;
+  Expr *index;
+
+  IndexExpr(int line, Expr *object, Expr *index) : Expr(line) {
+    this->object = object;
+    this->index = index;
+  }
+  string to_str() {
+    return "Index {object: " + object->to_str() + ", index: " +
+           index->to_str() + "}";
+  }
+};
+
+class FieldExpr : public Expr {
+ public:
+  Expr *object;
+  string name;
+
+  FieldExpr(int line, Expr *object, string name) : Expr(line) {
+    this->object = object;
+    this->name = name;
+  }
+  string to_str() {
+    return "Field {object: " + object->to_str() + ", name: \"" + name + "\"}";
+  }
+};
+
+class MethodCallExpr : public Expr {
+ public:
+  Expr *object;
+  string name;
+  vector<Expr *> args;
+
+  MethodCallExpr(int line, Expr *object, string name, vector<Expr *> args)
+      : Expr(line) {
+    this->object = object;
+    this->name = name;
+    this->args = args;
+  }
+  string to_str() {
+    stringstream s;
+    s << "MethodCall {object: " << object->to_str() << ", name: \"" << name
+      << "\", args: [";
+    for (size_t i = 0; i < args.size(); i++) {
+      s << (i ? ", " : "") << args[i]->to_str();
+    }
+    s << "]}";
+    return s.str();
+  }
+};
+
+class StructLitExpr : public Expr {
+ public:
+  string name;
+  vector<pair<string, Expr *>> fields;
+
+  StructLitExpr(int line, string name, vector<pair<string, Expr *>> fields)
+      : Expr(line) {
+    this->name = name;
+    this->fields = fields;
+  }
+  string to_str() {
+    stringstream s;
+    s << "StructLit {name: \"" << name << "\", fields: [";
+    for (size_t i = 0; i < fields.size(); i++) {
+      s << (i ? ", " : "") << fields[i].first << ": "
+        << fields[i].second->to_str();
+    }
+    s << "]}";
+    return s.str();
+  }
+};
+
+class Stmt {
+ public:
+  int line;
+  Stmt() = delete;
+  Stmt(int line) { this->line = line; }
+  virtual string to_str() { return "Stmt {line: something}"; }
+};
+
+class LetStmt : public Stmt {
+ public:
+  string name;
+  string type;
+  Expr *value;
+
+  LetStmt(int line, string name, string type, Expr *value) : Stmt(line) {
+    this->name = name;
+    this->type = type;
+    this->value = value;
+  }
+  string to_str() {
+    return "Let {name: \"" + name + "\", type: \"" + type +
+           "\", value: " + value->to_str() + "}";
+  }
+};
+
+class PrintStmt : public Stmt {
+ public:
+  Expr *value;
+
+  PrintStmt(int line, Expr *value) : Stmt(line) { this->value = value; }
+  string to_str() { return "Print {value: " + value->to_str() + "}"; }
+};
+
+class ExprStmt : public Stmt {
+ public:
+  Expr *expr;
+
+  ExprStmt(int line, Expr *expr) : Stmt(line) { this->expr = expr; }
+  string to_str() { return "ExprStmt {expr: " + expr->to_str() + "}"; }
+};
+
+class IfStmt : public Stmt {
+ public:
+  Expr *cond;
+  vector<Stmt *> then;
+  vector<Stmt *> otherwise;
+
+  IfStmt(int line, Expr *cond, vector<Stmt *> then, vector<Stmt *> otherwise)
+      : Stmt(line) {
+    this->cond = cond;
+    this->then = then;
+    this->otherwise = otherwise;
+  }
+  static string block(vector<Stmt *> stmts) {
+    string s = "[";
+    for (size_t i = 0; i < stmts.size(); i++) {
+      s += (i ? ", " : "") + stmts[i]->to_str();
+    }
+    return s + "]";
+  }
+  string to_str() {
+    return "If {cond: " + cond->to_str() + ", then: " + block(then) +
+           ", else: " + block(otherwise) + "}";
+  }
+};
+
+class WhileStmt : public Stmt {
+ public:
+  Expr *cond;
+  vector<Stmt *> body;
+
+  WhileStmt(int line, Expr *cond, vector<Stmt *> body) : Stmt(line) {
+    this->cond = cond;
+    this->body = body;
+  }
+  string to_str() {
+    return "While {cond: " + cond->to_str() +
+           ", body: " + IfStmt::block(body) + "}";
+  }
+};
+
+class Item {
+ public:
+  int line;
+  Item() = delete;
+  Item(int line) { this->line = line; }
+  virtual string to_str() { return "Item {line: something}"; }
+};
+
+class ImportItem : public Item {
+ public:
+  vector<string> path;
+  string alias;
+
+  ImportItem(int line, vector<string> path, string alias) : Item(line) {
+    this->path = path;
+    this->alias = alias;
+  }
+  string to_str() {
+    string s = "Import {path: [";
+    for (size_t i = 0; i < path.size(); i++) {
+      s += (i ? ", \"" : "\"") + path[i] + "\"";
+    }
+    return s + "], alias: \"" + alias + "\"}";
+  }
+};
+
+class ModItem : public Item {
+ public:
+  string name;
+  bool has_body;
+  vector<Item *> items;
+
+  ModItem(int line, string name, bool has_body, vector<Item *> items)
+      : Item(line) {
+    this->name = name;
+    this->has_body = has_body;
+    this->items = items;
+  }
+  string to_str() {
+    if (!has_body) {
+      return "Mod {name: \"" + name + "\"}";
+    }
+    string s = "Mod {name: \"" + name + "\", items: [";
+    for (size_t i = 0; i < items.size(); i++) {
+      s += (i ? ", " : "") + items[i]->to_str();
+    }
+    return s + "]}";
+  }
+};
+
+class ParseError {
+ public:
+  int line;
+  string details;
+  vector<string> expected;
+  ParseError(int line, string details, vector<string> expected = {}) {
+    this->line = line;
+    this->details = details;
+    this->expected = expected;
+  }
+  string to_str() {
+    stringstream s;
+    s << "ParseError {line: " << line << ", error: " << details << "}";
+    return s.str();
+  }
+};
+
+class Parser {
+ public:
+  vector<Token *> tokens;
+  vector<ParseError> errors;
+  size_t idx = 0;
+  bool struct_literals = true;
+  bool in_condition = false;
+  bool chained_assignment = true;
+  vector<string> expected;
+  size_t expected_at = 0;
+
+  Parser(vector<Token *> tokens) { this->tokens = tokens; }
+
+  Token *peek() { return idx < tokens.size() ? tokens[idx] : nullptr; }
+
+  int line() {
+    if (idx < tokens.size()) {
+      return tokens[idx]->line;
+    }
+    return tokens.empty() ? 1 : tokens.back()->line;
+  }
+
+  string peekSymbol() {
+    auto symbol = dynamic_cast<Symbol *>(peek());
+    return symbol ? symbol->value() : "";
+  }
+
+  void expect(string description) {
+    if (expected_at != idx) {
+      expected.clear();
+      expected_at = idx;
+    }
+    if (find(expected.begin(), expected.end(), description) == expected.end()) {
+      expected.push_back(description);
+    }
+  }
+
+  ParseError error() {
+    if (expected_at != idx) {
+      expected.clear();
+    }
+    string details = expected.size() == 1 ? "Expected " : "Expected one of ";
+    for (size_t i = 0; i < expected.size(); i++) {
+      details += (i ? ", " : "") + expected[i];
+    }
+    return ParseError(line(), expected.empty() ? "Unexpected token" : details,
+                      expected);
+  }
+
+  bool eatSymbol(string s) {
+    if (peekSymbol() == s) {
+      idx++;
+      return true;
+    }
+    expect("`" + s + "`");
+    return false;
+  }
+
+  void expectSymbol(string s) {
+    if (!eatSymbol(s)) {
+      throw error();
+    }
+  }
+
+  bool eatKeyword(string keyword) {
+    if (auto token = dynamic_cast<Keyword *>(peek());
+        token && REVERSE_KEYWORD[token->id] == keyword) {
+      idx++;
+      return true;
+    }
+    expect("`" + keyword + "`");
+    return false;
+  }
+
+  string expectIdent() {
+    if (auto ident = dynamic_cast<Identifier *>(peek())) {
+      idx++;
+      return ident->value;
+    }
+    expect("identifier");
+    throw error();
+  }
+
+  vector<Expr *> parseList(string close) {
+    vector<Expr *> items;
+    while (peekSymbol() != close) {
+      items.push_back(parseNested());
+      if (!eatSymbol(",")) {
+        break;
+      }
+    }
+    expectSymbol(close);
+    return items;
+  }
+
+  Expr *parseStructLit(int l, string name) {
+    vector<pair<string, Expr *>> fields;
+    while (peekSymbol() != "}") {
+      string field = expectIdent();
+      expectSymbol(":");
+      fields.push_back({field, parseNested()});
+      if (!eatSymbol(",")) {
+        break;
+      }
+    }
+    expectSymbol("}");
+    return new StructLitExpr(l, name, fields);
+  }
+
+  Expr *parseArray(int l) {
+    if (eatSymbol("]")) {
+      return new ArrayExpr(l, {});
+    }
+    Expr *first = parseNested();
+    if (eatSymbol(";")) {
+      Expr *count = parseNested();
+      expectSymbol("]");
+      return new RepeatExpr(l, first, count);
+    }
+    vector<Expr *> items{first};
+    if (eatSymbol(",")) {
+      auto rest = parseList("]");
+      items.insert(items.end(), rest.begin(), rest.end());
+    } else {
+      expectSymbol("]");
+    }
+    return new ArrayExpr(l, items);
+  }
+
+  Expr *parsePrimary() {
+    Token *token = peek();
+    int l = line();
+    if (auto literal = dynamic_cast<Literal *>(token)) {
+      idx++;
+      return new LiteralExpr(literal);
+    } else if (auto ident = dynamic_cast<Identifier *>(token)) {
+      idx++;
+      if (struct_literals && !in_condition && eatSymbol("{")) {
+        return parseStructLit(l, ident->value);
+      }
+      return new VariableExpr(ident->line, ident->value);
+    } else if (peekSymbol() == "(") {
+      idx++;
+      Expr *expr = parseNested();
+      expectSymbol(")");
+      return expr;
+    } else if (peekSymbol() == "[") {
+      idx++;
+      return parseArray(l);
+    }
+    expect("expression");
+    throw error();
+  }
+
+  Expr *parsePostfix() {
+    Expr *expr = parsePrimary();
+    while (true) {
+      int l = line();
+      if (eatSymbol("(")) {
+        expr = new CallExpr(l, expr, parseList(")"));
+      } else if (eatSymbol("[")) {
+        Expr *index = parseNested();
+        expectSymbol("]");
+        expr = new IndexExpr(l, expr, index);
+      } else if (eatSymbol(".")) {
+        string name = expectIdent();
+        if (eatSymbol("(")) {
+          expr = new MethodCallExpr(l, expr, name, parseList(")"));
+        } else {
+          expr = new FieldExpr(l, expr, name);
+        }
+      } else {
+        return expr;
+      }
+    }
+  }
+
+  Expr *parseUnary() {
+    int l = line();
+    string op = peekSymbol();
+    if (op == "!" || op == "-" || op == "~") {
+      idx++;
+      return new UnaryExpr(l, op, parseUnary());
+    } else if (op == "--") {
+      // there is no decrement operator, so --a is -(-a)
+      idx++;
+      return new UnaryExpr(l, "-", new UnaryExpr(l, "-", parseUnary()));
+    }
+    return parsePostfix();
+  }
+
+  Expr *parseBinary(int precedence) {
+    Expr *lhs = parseUnary();
+    while (true) {
+      // after an operand a--b is a - (-b), and a-- alone is an error
+      Lexer::splitSymbol(tokens, idx, "--");
+      auto op = BINARY_PRECEDENCE.find(peekSymbol());
+      if (op == BINARY_PRECEDENCE.end()) {
+        expect("operator");
+      }
+      if (op == BINARY_PRECEDENCE.end() || op->second < precedence) {
+        return lhs;
+      }
+      int l = line();
+      idx++;
+      lhs = new BinaryExpr(l, op->first, lhs, parseBinary(op->second + 1));
+    }
+  }
+
+  Expr *parseTernary() {
+    Expr *cond = parseBinary(1);
+    int l = line();
+    if (!eatSymbol("?")) {
+      return cond;
+    }
+    Expr *then = parseTernary();
+    expectSymbol(":");
+    return new TernaryExpr(l, cond, then, parseTernary());
+  }
+
+  Expr *parseExpr() {
+    Expr *target = parseTernary();
+    int l = line();
+    if (!eatSymbol("=")) {
+      return target;
+    }
+    // a[i] = x and a.f = x wait until the interpreter has arrays and structs
+    if (!dynamic_cast<VariableExpr *>(target)) {
+      throw ParseError(l, "Invalid assignment target");
+    }
+    if (chained_assignment) {
+      return new AssignExpr(l, target, parseExpr());
+    }
+    Expr *value = parseTernary();
+    if (peekSymbol() == "=") {
+      throw ParseError(line(), "Chained assignment is not allowed");
+    }
+    return new AssignExpr(l, target, value);
+  }
+
+  Expr *parseNested() {
+    bool condition = in_condition;
+    in_condition = false;
+    Expr *expr = parseExpr();
+    in_condition = condition;
+    return expr;
+  }
+
+  Expr *parseCondition() {
+    bool condition = in_condition;
+    in_condition = true;
+    Expr *expr = parseExpr();
+    in_condition = condition;
+    return expr;
+  }
+
+  string parsePath() {
+    string path = expectIdent();
+    while (eatSymbol("::")) {
+      path += "::" + expectIdent();
+    }
+    return path;
+  }
+
+  // the > closing a generic may be the first half of a >>, as in
+  // Vec<Vec<i32>>, so split it before matching
+  void expectCloser() {
+    Lexer::splitSymbol(tokens, idx, ">>");
+    expectSymbol(">");
+  }
+
+  string parseType() {
+    string type = parsePath();
+    if (eatSymbol("<")) {
+      type += "<" + parseType();
+      while (eatSymbol(",")) {
+        type += ", " + parseType();
+      }
+      expectCloser();
+      type += ">";
+    }
+    return type;
+  }
+
+  vector<Stmt *> parseBlock() {
+    expectSymbol("{");
+    vector<Stmt *> stmts;
+    while (peek() && peekSymbol() != "}") {
+      stmts.push_back(parseStatement());
+    }
+    expectSymbol("}");
+    return stmts;
+  }
+
+  Stmt *parseIf(int l) {
+    Expr *cond = parseCondition();
+    vector<Stmt *> then = parseBlock(), otherwise;
+    if (eatKeyword("else")) {
+      int else_line = line();
+      otherwise = eatKeyword("if") ? vector<Stmt *>{parseIf(else_line)}
+                                   : parseBlock();
+    }
+    return new IfStmt(l, cond, then, otherwise);
+  }
+
+  Stmt *parseStatement() {
+    int l = line();
+    Stmt *stmt;
+    if (eatKeyword("if")) {
+      return parseIf(l);
+    } else if (eatKeyword("while")) {
+      Expr *cond = parseCondition();
+      return new WhileStmt(l, cond, parseBlock());
+    } else if (eatKeyword("let")) {
+      string name = expectIdent();
+      string type;
+      if (eatSymbol(":")) {
+        type = parseType();
+      }
+      expectSymbol("=");
+      stmt = new LetStmt(l, name, type, parseExpr());
+    } else if (eatKeyword("print")) {
+      stmt = new PrintStmt(l, parseExpr());
+    } else {
+      stmt = new ExprStmt(l, parseExpr());
+    }
+    expectSymbol(";");
+    return stmt;
+  }
+
+  Item *parseItem() {
+    int l = line();
+    if (eatKeyword("import")) {
+      vector<string> path{expectIdent()};
+      while (eatSymbol("::")) {
+        path.push_back(expectIdent());
+      }
+      string alias;
+      if (eatKeyword("as")) {
+        alias = expectIdent();
+      }
+      expectSymbol(";");
+      return new ImportItem(l, path, alias);
+    } else if (eatKeyword("mod")) {
+      string name = expectIdent();
+      if (!eatSymbol("{")) {
+        expectSymbol(";");
+        return new ModItem(l, name, false, {});
+      }
+      vector<Item *> items;
+      while (!eatSymbol("}")) {
+        if (idx >= tokens.size()) {
+          throw error();
+        }
+        items.push_back(parseItem());
+      }
+      return new ModItem(l, name, true, items);
+    }
+    throw error();
+  }
+
+  vector<Item *> parseItems() {
+    vector<Item *> items;
+    in_condition = false;
+    try {
+      while (idx < tokens.size()) {
+        items.push_back(parseItem());
+      }
+    } catch (ParseError &error) {
+      errors.push_back(error);
+    }
+    return items;
+  }
+
+  vector<Stmt *> parseStatements() {
+    vector<Stmt *> stmts;
+    in_condition = false;
+    try {
+      while (idx < tokens.size()) {
+        stmts.push_back(parseStatement());
+      }
+    } catch (ParseError &error) {
+      errors.push_back(error);
+    }
+    return stmts;
+  }
+
+  Expr *parse() {
+    in_condition = false;
+    try {
+      Expr *expr = parseExpr();
+      if (idx < tokens.size()) {
+        throw error();
+      }
+      return expr;
+    } catch (ParseError &error) {
+      errors.push_back(error);
+      return nullptr;
+    }
+  }
+};
+
+#endif
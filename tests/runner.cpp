@@ -0,0 +1,750 @@
+#include <bits/stdc++.h>
+
+#define LEXER_COMPACT_TOKENS
+#include "../src/interpreter.cpp"
+
+using namespace std;
+
+// counts every heap allocation, so a check can assert a loop makes none
+static size_t allocations = 0;
+
+void *operator new(size_t size) {
+  allocations++;
+  if (void *memory = malloc(size ? size : 1)) {
+    return memory;
+  }
+  throw bad_alloc();
+}
+
+void operator delete(void *memory) noexcept { free(memory); }
+
+void operator delete(void *memory, size_t) noexcept { free(memory); }
+
+static_assert(!is_copy_constructible_v<Lexer> &&
+                  !is_move_constructible_v<Lexer> &&
+                  !is_copy_assignable_v<Lexer> && !is_move_assignable_v<Lexer>,
+              "a copied Lexer's cursor would point at the original's source");
+
+struct FixtureOptions {
+  LexerOptions lexer;
+  bool chained_assignment = true;
+  // parse the file as import/mod items instead of statements
+  bool items = false;
+};
+
+class FixtureRunner {
+ public:
+  bool update = false;
+  int passed = 0;
+  vector<string> failures;
+
+  static string readFile(const filesystem::path &path) {
+    ifstream file(path, ios::binary);
+    stringstream contents;
+    contents << file.rdbuf();
+    return contents.str();
+  }
+
+  static bool *flag(FixtureOptions &options, const string &name) {
+    LexerOptions &lexer = options.lexer;
+    const map<string, bool *> flags{
+        {"textual_operators", &lexer.textual_operators},
+        {"directives", &lexer.directives},
+        {"attributes", &lexer.attributes},
+        {"template_strings", &lexer.template_strings},
+        {"doc_comments", &lexer.doc_comments},
+        {"trivia", &lexer.trivia},
+        {"numbers_as_raw", &lexer.numbers_as_raw},
+        {"allow_leading_zeros", &lexer.allow_leading_zeros},
+        {"float_words", &lexer.float_words},
+        {"keyword_case_warnings", &lexer.keyword_case_warnings},
+        {"recover_to_delimiter", &lexer.recover_to_delimiter},
+        {"eof_token", &lexer.eof_token},
+        {"group_unknown", &lexer.group_unknown},
+        {"elementwise_operators", &lexer.elementwise_operators},
+        {"backslash_symbol", &lexer.backslash_symbol},
+        {"legacy_escapes", &lexer.legacy_escapes},
+        {"chained_assignment", &options.chained_assignment},
+        {"items", &options.items}};
+    auto it = flags.find(name);
+    return it == flags.end() ? nullptr : it->second;
+  }
+
+  // the first line may be `# options: a b !c`, setting a and b and clearing c
+  static FixtureOptions optionsFor(const string &source) {
+    FixtureOptions options;
+    const string prefix = "# options:";
+    if (source.compare(0, prefix.size(), prefix) != 0) {
+      return options;
+    }
+    stringstream names(source.substr(prefix.size(),
+                                     source.find('\n') - prefix.size()));
+    string name;
+    while (names >> name) {
+      bool value = name[0] != '!';
+      if (bool *target = flag(options, value ? name : name.substr(1))) {
+        *target = value;
+      } else {
+        throw runtime_error("Unknown fixture option `" + name + "`");
+      }
+    }
+    return options;
+  }
+
+  static string lexOutput(const string &source, FixtureOptions options) {
+    Lexer lexer(source, options.lexer);
+    lexer.lex();
+    stringstream out;
+    for (auto token : lexer.tokens) {
+      out << token->to_str() << endl;
+      for (auto &comment : token->leading) {
+        out << "  leading: " << comment << endl;
+      }
+      for (auto &comment : token->trailing) {
+        out << "  trailing: " << comment << endl;
+      }
+    }
+    for (auto warning : lexer.warnings) {
+      out << warning.to_str() << endl;
+    }
+    return out.str();
+  }
+
+  // restoring a compacted stream must give back a token of the same kind on
+  // the same line at every offset lex() produced
+  static string roundTrip(const string &source, FixtureOptions options) {
+    options.lexer.eof_token = false;
+    Lexer lexer(source, options.lexer);
+    lexer.lex();
+    auto compact = lexer.compact();
+    auto restored = lexer.restore(compact);
+    if (restored.size() != lexer.tokens.size()) {
+      return "restore gave " + to_string(restored.size()) + " of " +
+             to_string(lexer.tokens.size()) + " tokens";
+    }
+    for (size_t i = 0; i < restored.size(); i++) {
+      if (restored[i]->kind() != lexer.tokens[i]->kind() ||
+          restored[i]->offset != lexer.tokens[i]->offset ||
+          restored[i]->line != lexer.tokens[i]->line) {
+        return "restore differs at token " + to_string(i) + ": " +
+               restored[i]->to_str();
+      }
+    }
+    return "";
+  }
+
+  static string parseOutput(const string &source, FixtureOptions options) {
+    Lexer lexer(source, options.lexer);
+    lexer.lex();
+    Parser parser(lexer.tokens);
+    parser.chained_assignment = options.chained_assignment;
+    stringstream out;
+    if (options.items) {
+      for (auto item : parser.parseItems()) {
+        out << item->to_str() << endl;
+      }
+    } else {
+      for (auto stmt : parser.parseStatements()) {
+        out << stmt->to_str() << endl;
+      }
+    }
+    for (auto error : parser.errors) {
+      out << error.to_str() << endl;
+    }
+    return out.str();
+  }
+
+  static string evalOutput(const string &source, FixtureOptions options) {
+    Lexer lexer(source, options.lexer);
+    lexer.lex();
+    Parser parser(lexer.tokens);
+    parser.chained_assignment = options.chained_assignment;
+    auto program = parser.parseStatements();
+    stringstream out;
+    for (auto error : parser.errors) {
+      out << error.to_str() << endl;
+    }
+    Interpreter interpreter;
+    interpreter.out = &out;
+    interpreter.run(program);
+    for (auto error : interpreter.errors) {
+      out << error.to_str() << endl;
+    }
+    return out.str();
+  }
+
+  static string firstDifference(const string &expected, const string &actual) {
+    stringstream e(expected), a(actual);
+    string el, al;
+    for (int line = 1;; line++) {
+      bool more_e = (bool)getline(e, el), more_a = (bool)getline(a, al);
+      if (!more_e && !more_a) {
+        return "";
+      } else if (!more_e || !more_a || el != al) {
+        return "line " + to_string(line) + "\n    expected: " +
+               (more_e ? el : "<end>") + "\n    actual:   " +
+               (more_a ? al : "<end>");
+      }
+    }
+  }
+
+  void check(const filesystem::path &input, const string &extension,
+             function<string(const string &, FixtureOptions)> render) {
+    string source = readFile(input);
+    string actual = render(source, optionsFor(source));
+    auto golden = filesystem::path(input).replace_extension(extension);
+    if (extension == ".tokens") {
+      string error = roundTrip(source, optionsFor(source));
+      if (!error.empty()) {
+        failures.push_back(input.string() + ": " + error);
+        return;
+      }
+    }
+    if (update) {
+      ofstream(golden, ios::binary) << actual;
+      passed++;
+      return;
+    } else if (!filesystem::exists(golden)) {
+      failures.push_back(golden.string() + ": missing golden file");
+      return;
+    }
+    string difference = firstDifference(readFile(golden), actual);
+    if (difference.empty()) {
+      passed++;
+    } else {
+      failures.push_back(golden.string() + ": " + difference);
+    }
+  }
+
+  void expect(bool ok, string name) {
+    if (ok) {
+      passed++;
+    } else {
+      failures.push_back("check: " + name);
+    }
+  }
+
+  static Token *spanned(int line, size_t offset, size_t length) {
+    auto token = new Identifier(line, "x");
+    token->offset = offset;
+    token->length = length;
+    return token;
+  }
+
+  // behaviour that has no token stream to compare against a golden file
+  void checks() {
+    TokenStreamBuilder builder;
+    expect(builder.push(spanned(1, 0, 3)), "builder accepts the first token");
+    expect(!builder.push(spanned(1, 1, 1)), "builder rejects an overlap");
+    expect(builder.push(spanned(1, 3, 1)), "builder accepts an adjacent token");
+    expect(!builder.push(spanned(1, 3, 1)), "builder rejects a repeat");
+    expect(!builder.push(spanned(0, 5, 1)), "builder rejects an earlier line");
+
+    Lexer viewport("a\nb c\nd\ne");
+    viewport.lex();
+    auto middle = viewport.tokensInLines(2, 3);
+    expect(middle.size() == 3 && middle.front()->line == 2 &&
+               middle.back()->line == 3,
+           "tokensInLines keeps the tokens of lines 2 to 3");
+
+    vector<pair<size_t, size_t>> reports;
+    LexerOptions chunked;
+    chunked.progress_interval = 4;
+    chunked.progress = [&reports](size_t done, size_t total) {
+      reports.push_back({done, total});
+    };
+    Lexer progress("a b c d e ", chunked);
+    progress.lex();
+    expect(reports == vector<pair<size_t, size_t>>{{4, 10}, {8, 10}},
+           "progress fires every 4 bytes of a 10 byte input");
+
+    Lexer generic("Vec<Vec<i32>>");
+    generic.lex();
+    Parser closers(generic.tokens);
+    expect(closers.parseType() == "Vec<Vec<i32>>" &&
+               closers.tokens.size() == generic.tokens.size() + 1 &&
+               ((Symbol *)generic.tokens.back())->value() == ">>",
+           "the parser splits >> into two closers in its own stream");
+
+    string dense;
+    for (int i = 0; i < 200; i++) {
+      dense += "a += b << c && !d || e -> f :: g ..= h ";
+    }
+    Lexer operators(dense);
+    operators.lex();
+    size_t spelled = 0, before = allocations;
+    for (auto token : operators.tokens) {
+      if (auto symbol = dynamic_cast<Symbol *>(token)) {
+        spelled += symbol->value().size();
+      }
+    }
+    bool allocated = allocations != before;
+    expect(spelled > 0 && !allocated,
+           "spelling symbols reads the static table without allocating");
+
+    set<char> initials;
+    for (auto &[spelling, id] : SYMBOLS) {
+      if (spelling.size() > 1) {
+        initials.insert(spelling[0]);
+      }
+    }
+    for (char initial : initials) {
+      Lexer last(string("a ") + initial);
+      last.lex();
+      auto symbol = last.tokens.size() == 2
+                        ? dynamic_cast<Symbol *>(last.tokens[1])
+                        : nullptr;
+      expect(symbol && symbol->value() == string(1, initial) &&
+                 symbol->length == 1,
+             string("`") + initial + "` as the last byte is one symbol");
+    }
+
+    expect(Lexer::isValid("let a = 1 + 2;"), "isValid accepts clean input");
+    auto first_error = Lexer::validate("a $ b @ c");
+    expect(first_error && first_error->text == "$" &&
+               !Lexer::isValid("a $ b"),
+           "validate stops at the first error");
+
+    LexerOptions short_strings;
+    short_strings.max_string_length = 3;
+    Lexer sized("\"abc\" \"abcd\"", short_strings);
+    sized.lex();
+    expect(sized.warnings.size() == 1 && sized.tokens.size() == 2,
+           "max_string_length warns only about the longer string");
+
+    auto indent = [](string source) { return Lexer(source).detectIndent(); };
+    expect(indent("a\n\tb\n\t\tc\n").kind == IndentKind::Tabs,
+           "detectIndent finds tabs");
+    expect(indent("a\n  b\n    c\n").width == 2 &&
+               indent("a\n    b\n        c\n").width == 4 &&
+               indent("a\n    b\n").kind == IndentKind::Spaces,
+           "detectIndent finds the space width");
+    expect(indent("a\n\tb\n  c\n").kind == IndentKind::Mixed,
+           "detectIndent reports mixed indentation");
+
+    auto cut = Lexer::validate("print \"abc");
+    auto escape = Lexer::validate("print \"a\\q\";");
+    expect(cut && cut->error_kind == LexErrorKind::Incomplete,
+           "an unterminated string at the end is Incomplete");
+    expect(escape && escape->error_kind == LexErrorKind::InvalidEscape,
+           "a bad escape is InvalidEscape, not Incomplete");
+    auto incomplete = [](string source) {
+      Lexer lexer(source);
+      lexer.lex();
+      return lexer.isIncomplete();
+    };
+    expect(incomplete("a +") && incomplete("a \\") &&
+               incomplete("print \"abc"),
+           "isIncomplete for an extendable operator, \\ or open string");
+    expect(!incomplete("a + ") && !incomplete("a;") &&
+               !incomplete("print \"a\\q\";"),
+           "isIncomplete is false once the last token is settled");
+
+    Lexer registered("a <=> b @now");
+    registered.registerOperator("<=>");
+    registered.registerLiteral(
+        [](const string &source, size_t at, size_t &length) -> Token * {
+          if (source.compare(at, 4, "@now") != 0) {
+            return nullptr;
+          }
+          length = 4;
+          return new Literal(0, LiteralType::String, "now");
+        });
+    registered.lex();
+    auto compact = registered.compact();
+    auto restored = registered.restore(compact);
+    expect(restored.size() == 4 &&
+               ((Symbol *)restored[1])->value() == "<=>" &&
+               restored[3]->kind() == TokenKind::Literal,
+           "restore keeps registered operators and literal lexers");
+
+    LexerOptions raw;
+    raw.numbers_as_raw = true;
+    Lexer bignums(
+        "3.14159265358979323846 99999999999999999999 0xFFFFFFFFFFFFFFFFF", raw);
+    bignums.lex();
+    auto number = [&bignums](size_t i) {
+      return (Literal *)bignums.tokens[i];
+    };
+    expect(bignums.tokens.size() == 3 &&
+               number(0)->type == LiteralType::Number &&
+               number(0)->value == "3.14159265358979323846" &&
+               number(1)->value == "99999999999999999999" &&
+               number(2)->type == LiteralType::Number,
+           "numbers_as_raw keeps each spelling as a Number literal");
+
+    LexerOptions nil;
+    nil.null_word = "nil";
+    Lexer nulls("null nil"), nils("null nil", nil);
+    nulls.lex();
+    nils.lex();
+    auto isNull = [](Token *token) {
+      auto literal = dynamic_cast<Literal *>(token);
+      return literal && literal->type == LiteralType::Null;
+    };
+    expect(isNull(nulls.tokens[0]) && !isNull(nulls.tokens[1]) &&
+               !isNull(nils.tokens[0]) && isNull(nils.tokens[1]),
+           "null_word picks the one word lexed as Null");
+
+    LexerOptions dashes;
+    dashes.line_comment_prefixes = {"--"};
+    Lexer sql("a -- note\nb", dashes), hashed("a # note\nb");
+    sql.lex();
+    hashed.lex();
+    expect(sql.tokens.size() == 2 && sql.tokens[1]->line == 2 &&
+               hashed.tokens.size() == 2,
+           "-- and # each start a line comment when listed");
+
+    string mixed =
+        "let s = \"caf\xc3\xa9\" + r\"raw\"; # note\nf(1.5, 'c') $ 0x1F";
+    Lexer all(mixed), scan(mixed);
+    all.lex();
+    vector<TokenKind> lexed, scanned;
+    for (auto token : all.tokens) {
+      lexed.push_back(token->kind());
+    }
+    while (auto kind = scan.nextKind()) {
+      scanned.push_back(*kind);
+    }
+    expect(scanned == lexed && scan.tokens.empty() && scan.stats.total == 0,
+           "nextKind gives lex()'s kinds without keeping or counting tokens");
+
+    LexerOptions folded;
+    folded.attributes = folded.template_strings = true;
+    Lexer attribute("#[a] x", folded), hole("`a ${b} c`", folded);
+    attribute.lex();
+    hole.lex();
+    expect(attribute.stats.total == 2 && hole.stats.total == 1 &&
+               hole.stats.kinds[TokenKind::Template] == 1,
+           "stats count the folded tokens, not what was folded into them");
+
+    TokenStats half;
+    LexerOptions halfway;
+    halfway.progress_interval = 6;
+    Lexer streamed("a b c d e $ ", halfway);
+    streamed.options.progress = [&streamed, &half](size_t done, size_t) {
+      if (done == 6) {
+        half = streamed.stats;
+      }
+    };
+    streamed.lex();
+    expect(half.total == 3 && half.bytes == 6 && half.errors == 0 &&
+               streamed.stats.total == 6 && streamed.stats.errors == 1,
+           "stats part way through a stream cover what was lexed so far");
+
+    auto grouped = Lexer::linesWithTokens("a b\n\"x\ny\"\nc");
+    expect(grouped.size() == 3 && grouped[0].first == 1 &&
+               grouped[0].second.size() == 2 && grouped[1].first == 2 &&
+               grouped[2].first == 4,
+           "linesWithTokens files a multi-line string under its first line");
+
+    const vector<pair<char, ByteClass>> bytes{
+        {'a', ByteClass::IdentStart},  {'_', ByteClass::IdentStart},
+        {'7', ByteClass::Digit},       {'"', ByteClass::StringQuote},
+        {'\'', ByteClass::CharQuote},  {'\n', ByteClass::Newline},
+        {'\t', ByteClass::Whitespace}, {'#', ByteClass::Hash},
+        {'+', ByteClass::SymbolStart}, {'$', ByteClass::Other}};
+    for (auto [byte, cls] : bytes) {
+      expect(Lexer::classifyByte(byte) == cls,
+             "classifyByte(" + to_string((int)byte) + ")");
+    }
+
+    Lexer dense_lines("a b c\nd\n\ne f g h ( )");
+    dense_lines.lex();
+    expect(Lexer::tokensPerLine(dense_lines.tokens) ==
+                   vector<size_t>{3, 1, 0, 6} &&
+               Lexer::maxLineLengthInTokens(dense_lines.tokens) == 6,
+           "tokensPerLine counts blank lines as 0 and the max is 6");
+
+    Lexer bounded("a <==> b");
+    LexerOptions longer;
+    longer.max_operator_length = 4;
+    Lexer raised("a <==> b", longer);
+    expect(!bounded.registerOperator("<==>") &&
+               raised.registerOperator("<==>"),
+           "a 4-char operator needs max_operator_length 4");
+    raised.lex();
+    expect(raised.tokens.size() == 3 &&
+               ((Symbol *)raised.tokens[1])->value() == "<==>",
+           "a registered 4-char operator lexes as one symbol");
+
+    Position end = Lexer::endPosition("ab\ncde\n"),
+             mid = Lexer::endPosition("ab\ncde");
+    expect(end.line == 3 && end.col == 1 && mid.line == 2 && mid.col == 4,
+           "endPosition is just past the last byte");
+
+    Lexer decrement("--a");
+    decrement.lex();
+    Parser negations(decrement.tokens);
+    expect(negations.parse() && decrement.tokens.size() == 2 &&
+               ((Symbol *)decrement.tokens[0])->value() == "--",
+           "the parser reads --a as two negations, the lexer keeps --");
+
+    LexerOptions hash_only;
+    hash_only.line_comment_prefixes = {"#"};
+    Lexer slashes("a // b", hash_only);
+    slashes.lex();
+    expect(slashes.tokens.size() == 4,
+           "// is / / without \"//\" in line_comment_prefixes");
+
+    LexerOptions no_blocks;
+    no_blocks.block_comment_delims = {"", ""};
+    Lexer star("a /* b", no_blocks);
+    star.lex();
+    expect(star.tokens.size() == 4, "/* is / * without block_comment_delims");
+
+    string plain = "fn f(a) { return a * 2.5; } # done\n/* x */ \"s\" 'c' $";
+    Lexer fast(plain), slow(plain);
+    slow.ascii = false;
+    fast.lex();
+    slow.lex();
+    bool same = fast.ascii && fast.tokens.size() == slow.tokens.size();
+    for (size_t i = 0; same && i < fast.tokens.size(); i++) {
+      same = fast.tokens[i]->to_str() == slow.tokens[i]->to_str() &&
+             fast.tokens[i]->offset == slow.tokens[i]->offset;
+    }
+    expect(same && !Lexer("caf\xc3\xa9").ascii,
+           "ASCII input lexes the same with and without the fast path");
+
+    auto folded_value = Interpreter::evaluate("2 + 3 * 4");
+    expect(folded_value->type == LiteralType::Int &&
+               folded_value->value == "14",
+           "evaluate folds 2 + 3 * 4 to 14");
+    auto stage = [](string input) -> string {
+      try {
+        Interpreter::evaluate(input);
+      } catch (CompileError &error) {
+        return error.stage;
+      }
+      return "";
+    };
+    expect(stage("2 +") == "parse" && stage("1 $ 2") == "lex" &&
+               stage("1 / 0") == "eval",
+           "evaluate raises a CompileError naming the failing stage");
+
+    string html = Lexer::highlightHtml("if a < b && c {}");
+    expect(html.find("<span class=\"kw\">if</span>") == 0 &&
+               html.find("<span class=\"sym\">&lt;</span>") !=
+                   string::npos &&
+               html.find("<span class=\"sym\">&amp;&amp;</span>") !=
+                   string::npos,
+           "highlightHtml marks keywords kw and escapes < and &");
+
+    LexerOptions quoted, unseparated;
+    quoted.digit_separator = '\'';
+    unseparated.digit_separator = 0;
+    Lexer apostrophes("1'000 0xFF'FF", quoted);
+    Lexer plain_digits("1_000", unseparated);
+    apostrophes.lex();
+    plain_digits.lex();
+    expect(apostrophes.tokens.size() == 2 &&
+               ((Literal *)apostrophes.tokens[0])->value == "1'000" &&
+               ((Literal *)apostrophes.tokens[1])->value == "0xFF'FF",
+           "digit_separator ' groups 1'000 and 0xFF'FF");
+    auto suffixed = plain_digits.tokens.size() == 1
+                        ? dynamic_cast<Invalid *>(plain_digits.tokens[0])
+                        : nullptr;
+    expect(suffixed && suffixed->details == "Unknown number suffix `_000`",
+           "digit_separator 0 leaves _000 as an unknown suffix on 1");
+
+    LexerOptions aliased;
+    aliased.keyword_aliases = {{"FUNC", "fn"}, {"Function", "fn"}};
+    Lexer aliases("FUNC Function fn func", aliased);
+    aliases.lex();
+    auto isFn = [&aliases](size_t i) {
+      auto keyword = dynamic_cast<Keyword *>(aliases.tokens[i]);
+      return keyword && keyword->id == KEYWORDS.at("fn");
+    };
+    expect(aliases.tokens.size() == 4 && isFn(0) && isFn(1) && isFn(2) &&
+               aliases.tokens[3]->kind() == TokenKind::Identifier,
+           "keyword_aliases map FUNC and Function to fn and keep fn");
+
+    Lexer chain("a\n  .b()\n  .c()");
+    chain.lex();
+    auto boundaries = Lexer::chainBoundaries(chain.tokens);
+    expect(boundaries == vector<size_t>{1, 5} &&
+               chain.tokens[1]->line == 2 && chain.tokens[5]->line == 3,
+           "chainBoundaries finds the . starting lines 2 and 3");
+
+    Lexer used("fn f(a) { if a { return a + 1; } return a + 2; }");
+    used.lex();
+    expect(Lexer::distinctSymbols(used.tokens) ==
+                   set<string>{"(", ")", "{", "}", "+", ";"} &&
+               Lexer::distinctKeywords(used.tokens) ==
+                   set<string>{"fn", "if", "return"},
+           "distinctSymbols and distinctKeywords list each spelling once");
+
+    string commented = "a /* one\ntwo */ b # three\n  c";
+    string stripped = Lexer::stripComments(commented);
+    Lexer before_strip(commented), after_strip(stripped);
+    before_strip.lex();
+    after_strip.lex();
+    bool kept = stripped.size() == commented.size() &&
+                after_strip.tokens.size() == 3 &&
+                before_strip.tokens.size() == 3;
+    for (size_t i = 0; kept && i < 3; i++) {
+      kept = after_strip.tokens[i]->line == before_strip.tokens[i]->line &&
+             after_strip.tokens[i]->col == before_strip.tokens[i]->col;
+    }
+    expect(kept && after_strip.tokens[1]->line == 2 &&
+               after_strip.tokens[1]->col == 8,
+           "stripComments keeps the line and col of the tokens after it");
+
+    string statements;
+    for (int i = 0; i < 500; i++) {
+      statements += "let value = compute(first, second);\n";
+    }
+    auto lexAllocations = [&statements](size_t capacity) {
+      LexerOptions sized;
+      sized.token_capacity = capacity;
+      size_t start = allocations;
+      Lexer lexer(statements, sized);
+      lexer.lex();
+      return allocations - start;
+    };
+    size_t reserved = lexAllocations(0), grown = lexAllocations(1);
+    expect(reserved < grown,
+           "reserving size / 4 tokens allocates less than token_capacity 1 (" +
+               to_string(reserved) + " vs " + to_string(grown) + ")");
+
+    string spanned_source = "let a = 1;\nprint a;\n";
+    Lexer spans(spanned_source);
+    size_t span_start = spans.offsetOf(1, 5), span_end = spans.offsetOf(2, 6);
+    expect(span_start == 4 && span_end == 16 &&
+               spanned_source.substr(span_start, span_end - span_start) ==
+                   "a = 1;\nprint" &&
+               spans.offsetOf(1, 11) == 10 &&
+               spans.offsetOf(1, 12) == string::npos &&
+               spans.offsetOf(4, 1) == string::npos &&
+               spans.offsetOf(0, 1) == string::npos,
+           "offsetOf maps --span positions and rejects ones past a line");
+
+    LexerOptions with_eof;
+    with_eof.eof_token = true;
+    for (string blank : {"", " \t\n\n ", "# note\n/* block */ // line"}) {
+      Lexer bare(blank), ended(blank, with_eof);
+      bare.lex();
+      ended.lex();
+      expect(bare.tokens.empty() && ended.tokens.size() == 1 &&
+                 ended.tokens[0]->kind() == TokenKind::Eof,
+             "no tokens, or only Eof, for \"" + blank + "\"");
+    }
+
+    Lexer exponent("1e-5 x");
+    exponent.lex();
+    expect(exponent.tokens.size() == 2 && exponent.tokens[0]->offset == 0 &&
+               exponent.tokens[0]->length == 4 &&
+               exponent.tokens[1]->offset == 5,
+           "1e-5 is one token at offset 0 with length 4");
+
+    Lexer words("a and b");
+    words.lex();
+    expect(words.tokens.size() == 3 &&
+               all_of(words.tokens.begin(), words.tokens.end(),
+                      [](Token *token) {
+                        return token->kind() == TokenKind::Identifier;
+                      }),
+           "without textual_operators a and b is three identifiers");
+
+    auto nested = Lexer::validate("/* /* x */");
+    auto deeper = Lexer::validate("/* /* x");
+    expect(nested && deeper &&
+               nested->details == "Unterminated block comment (1 level open)" &&
+               deeper->details == "Unterminated block comment (2 levels open)",
+           "unterminated comments count 1 level or 2 levels open");
+
+    auto tables = Lexer::grammarTables();
+    LexerOptions everything;
+    everything.elementwise_operators = everything.backslash_symbol = true;
+    auto all_symbols = Lexer::grammarTables(everything).symbols;
+    auto exported = [](const vector<string> &symbols, string symbol) {
+      return find(symbols.begin(), symbols.end(), symbol) != symbols.end();
+    };
+    expect(tables.keywords ==
+               vector<string>(REVERSE_KEYWORD,
+                              REVERSE_KEYWORD + size(REVERSE_KEYWORD)),
+           "grammarTables exports REVERSE_KEYWORD");
+    expect(!exported(tables.symbols, ".*") &&
+               !exported(tables.symbols, "\\") &&
+               exported(all_symbols, ".*") && exported(all_symbols, "\\"),
+           "grammarTables exports only the symbols the options enable");
+    bool matched = true;
+    for (string spelling :
+         {"42", "1_000", "7u8", "0x1F", "0b1010i32", "0o17", ".5", "5.",
+          "1e-5", "2.5e+10", "1.5f32", "3f64", "\"a\\nb\"", "r#\"q\"\"#",
+          "b\"x\"", "b'A'", "'a'", "'\\u{41}'", "true", "null"}) {
+      Lexer literal(spelling);
+      literal.lex();
+      auto token = literal.tokens.size() == 1
+                       ? dynamic_cast<Literal *>(literal.tokens[0])
+                       : nullptr;
+      bool found = false;
+      for (auto &[name, pattern] : tables.literal_patterns) {
+        found = found ||
+                (token && name == LITERAL_TYPE_NAMES[(int)token->type] &&
+                 regex_match(spelling, regex(pattern)));
+      }
+      matched = matched && found;
+    }
+    expect(matched, "each literal lexed matches its exported pattern");
+
+    Lexer path("a::b c..=d");
+    path.lex();
+    expect(path.tokens.size() == 6 && path.tokens[1]->col == 2 &&
+               path.tokens[2]->col == 4 && path.tokens[3]->col == 6 &&
+               path.tokens[4]->col == 7 && path.tokens[5]->col == 10,
+           "the token after a multi-char symbol starts past all its bytes");
+
+    string program =
+        "let s = \"a\nb\"; # note\n/* x\ny */ f('c', 1.5e-3)\n"
+        "  r\"raw\" b'A' a::b >>= 0x1F;\n'\\x41'\n$";
+    Lexer positioned(program);
+    positioned.lex();
+    bool placed = !positioned.tokens.empty();
+    for (auto token : positioned.tokens) {
+      size_t newline = token->offset == 0
+                           ? string::npos
+                           : program.rfind('\n', token->offset - 1);
+      int line = 1 + count(program.begin(), program.begin() + token->offset,
+                           '\n');
+      int col = newline == string::npos ? token->offset + 1
+                                         : token->offset - newline;
+      placed = placed && token->line == line && token->col == col;
+    }
+    expect(placed, "every token's line and col match its offset in the source");
+  }
+
+  void run(const filesystem::path &dir, const string &extension,
+           function<string(const string &, FixtureOptions)> render) {
+    if (!filesystem::exists(dir)) {
+      return;
+    }
+    vector<filesystem::path> inputs;
+    for (auto &entry : filesystem::directory_iterator(dir)) {
+      if (entry.path().extension() == ".lang") {
+        inputs.push_back(entry.path());
+      }
+    }
+    sort(inputs.begin(), inputs.end());
+    for (auto &input : inputs) {
+      check(input, extension, render);
+    }
+  }
+};
+
+int main(int argc, char *argv[]) {
+  filesystem::path root = argc > 1 ? argv[1] : "tests/fixtures";
+  FixtureRunner runner;
+  runner.update = getenv("UPDATE_FIXTURES") != nullptr;
+  runner.checks();
+  runner.run(root, ".tokens", FixtureRunner::lexOutput);
+  runner.run(root / "parse", ".ast", FixtureRunner::parseOutput);
+  runner.run(root / "eval", ".out", FixtureRunner::evalOutput);
+  for (auto &failure : runner.failures) {
+    cout << "FAIL " << failure << endl;
+  }
+  cout << runner.passed << " passed, " << runner.failures.size()
+       << " failed" << (runner.update ? " (golden files updated)" : "")
+       << endl;
+  return runner.failures.empty() ? 0 : 1;
+}